@@ -0,0 +1,388 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::proxy::{self, ProxyError};
+
+pub(crate) const DEFAULT_CATALOG_URL: &str = "https://server.portables.dev";
+// Overrides the catalog server, e.g. to point at a local mock server
+pub(crate) const CATALOG_URL_ENV: &str = "PORTABLESOURCE_CATALOG_URL";
+
+const DEFAULT_PAGE_SIZE: u32 = 10;
+const MAX_PAGE_SIZE: u32 = 100;
+// The server only offers api/repositories/top?limit=N. Pages past the first fetch the entries
+// before them as well, and search filters this many of the top repositories locally.
+const MAX_LISTED: u32 = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Repository {
+    #[serde(default)]
+    pub(crate) id: Option<i64>,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) description: String,
+    #[serde(default)]
+    pub(crate) repository_url: Option<String>,
+    #[serde(default)]
+    pub(crate) download_count: Option<u64>,
+    #[serde(default)]
+    pub(crate) uploaded_by_username: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CatalogPage {
+    pub(crate) repositories: Vec<Repository>,
+    pub(crate) limit: u32,
+    pub(crate) offset: u32,
+    pub(crate) has_more: bool,
+    pub(crate) fetched_at: DateTime<Utc>,
+    // Served from the on-disk cache because the server could not be reached
+    pub(crate) stale: bool,
+}
+
+// One cached catalog response, stored as <install>/cache/catalog/<key>.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    fetched_at: DateTime<Utc>,
+    etag: Option<String>,
+    repositories: Vec<Repository>,
+}
+
+pub(crate) struct CatalogCache {
+    dir: PathBuf,
+}
+
+impl CatalogCache {
+    pub(crate) fn for_install(install_dir: &Path) -> Self {
+        Self { dir: install_dir.join("cache").join("catalog") }
+    }
+
+    fn load(&self, key: &str) -> Option<CacheEntry> {
+        let raw = std::fs::read(self.dir.join(format!("{}.json", key))).ok()?;
+        serde_json::from_slice(&raw).ok()
+    }
+
+    fn store(&self, key: &str, entry: &CacheEntry) {
+        let path = self.dir.join(format!("{}.json", key));
+        let tmp = self.dir.join(format!("{}.json.tmp", key));
+        let result = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&tmp, serde_json::to_vec_pretty(entry).unwrap_or_default()))
+            .and_then(|_| std::fs::rename(&tmp, &path));
+        if let Err(e) = result {
+            log::warn!("Failed to write catalog cache {}: {}", path.display(), e);
+        }
+    }
+}
+
+// File name for the cached top list of a given length
+fn cache_key(count: u32) -> String {
+    format!("top_l{}", count)
+}
+
+// Case-insensitive match on name or description; an empty query matches everything
+fn matches_query(repo: &Repository, query: &str) -> bool {
+    let query = query.trim().to_lowercase();
+    query.is_empty() || repo.name.to_lowercase().contains(&query) || repo.description.to_lowercase().contains(&query)
+}
+
+// The top list as fetched or cached, before it is cut into a page
+struct Listing {
+    repositories: Vec<Repository>,
+    fetched_at: DateTime<Utc>,
+    stale: bool,
+}
+
+impl Listing {
+    fn page(self, limit: u32, offset: u32, filter: impl Fn(&Repository) -> bool) -> CatalogPage {
+        let mut matching = self.repositories.into_iter().filter(|r| filter(r)).skip(offset as usize);
+        let repositories: Vec<Repository> = matching.by_ref().take(limit as usize).collect();
+        CatalogPage {
+            has_more: matching.next().is_some(),
+            repositories,
+            limit,
+            offset,
+            fetched_at: self.fetched_at,
+            stale: self.stale,
+        }
+    }
+}
+
+// Failures where an older copy is better than nothing
+fn is_offline_error(e: &ProxyError) -> bool {
+    match e {
+        ProxyError::Timeout { .. } | ProxyError::Network { .. } => true,
+        ProxyError::BadStatus { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CatalogResponse {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    repositories: Vec<Repository>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Clone)]
+pub(crate) struct CatalogClient {
+    base_url: reqwest::Url,
+    client: reqwest::Client,
+}
+
+impl CatalogClient {
+    pub(crate) fn new(base_url: &str) -> Result<Self, ProxyError> {
+        let mut base_url = reqwest::Url::parse(base_url).map_err(|e| ProxyError::InvalidUrl { message: e.to_string() })?;
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        let host = base_url.host_str().unwrap_or_default().to_string();
+        let client = proxy::build_client(vec![host])?;
+        Ok(Self { base_url, client })
+    }
+
+    // Uses PORTABLESOURCE_CATALOG_URL when set, the public server otherwise
+    pub(crate) fn from_env() -> Self {
+        if let Ok(url) = std::env::var(CATALOG_URL_ENV) {
+            match Self::new(&url) {
+                Ok(client) => return client,
+                Err(e) => log::warn!("Ignoring {}={}: {}", CATALOG_URL_ENV, url, e),
+            }
+        }
+        Self::new(DEFAULT_CATALOG_URL).expect("default catalog URL is valid")
+    }
+
+    pub(crate) fn host(&self) -> Option<String> {
+        self.base_url.host_str().map(|h| h.to_string())
+    }
+
+    pub(crate) async fn top(&self, cache: Option<&CatalogCache>, limit: Option<u32>, offset: Option<u32>) -> Result<CatalogPage, ProxyError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let offset = offset.unwrap_or(0).min(MAX_LISTED);
+        // One more than shown tells whether another page exists
+        let count = (offset + limit + 1).min(MAX_LISTED);
+        Ok(self.list(cache, count).await?.page(limit, offset, |_| true))
+    }
+
+    pub(crate) async fn search(
+        &self,
+        cache: Option<&CatalogCache>,
+        query: &str,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<CatalogPage, ProxyError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let offset = offset.unwrap_or(0);
+        Ok(self.list(cache, MAX_LISTED).await?.page(limit, offset, |repo| matches_query(repo, query)))
+    }
+
+    // The first `count` top repositories, revalidated against the cache and served from it offline
+    async fn list(&self, cache: Option<&CatalogCache>, count: u32) -> Result<Listing, ProxyError> {
+        let mut url = self
+            .base_url
+            .join("api/repositories/top")
+            .map_err(|e| ProxyError::InvalidUrl { message: e.to_string() })?;
+        url.query_pairs_mut().append_pair("limit", &count.to_string());
+
+        let key = cache_key(count);
+        // Entries for another server (e.g. a mock) are not valid here
+        let cached = cache.and_then(|c| c.load(&key)).filter(|entry| entry.url == url.as_str());
+        let listing = |entry: CacheEntry, stale: bool| Listing { repositories: entry.repositories, fetched_at: entry.fetched_at, stale };
+
+        match self.fetch(url.clone(), cached.as_ref().and_then(|c| c.etag.as_deref())).await {
+            Ok(Fetched::NotModified) => match cached {
+                Some(mut entry) => {
+                    entry.fetched_at = Utc::now();
+                    if let Some(cache) = cache {
+                        cache.store(&key, &entry);
+                    }
+                    Ok(listing(entry, false))
+                }
+                None => Err(ProxyError::InvalidResponse { message: "Server answered 304 without a cached copy".to_string() }),
+            },
+            Ok(Fetched::Body { body, etag }) => {
+                let response: CatalogResponse = serde_json::from_slice(&body)
+                    .map_err(|e| ProxyError::InvalidResponse { message: e.to_string() })?;
+                if !response.success {
+                    return Err(ProxyError::InvalidResponse {
+                        message: response.message.unwrap_or_else(|| "Catalog server reported failure".to_string()),
+                    });
+                }
+                let entry = CacheEntry { url: url.to_string(), fetched_at: Utc::now(), etag, repositories: response.repositories };
+                if let Some(cache) = cache {
+                    cache.store(&key, &entry);
+                }
+                Ok(listing(entry, false))
+            }
+            Err(e) if is_offline_error(&e) => match cached {
+                Some(entry) => {
+                    log::warn!("Catalog unreachable ({}), serving cache from {}", e, entry.fetched_at);
+                    Ok(listing(entry, true))
+                }
+                None => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn fetch(&self, url: reqwest::Url, etag: Option<&str>) -> Result<Fetched, ProxyError> {
+        let mut request = self.client.get(url.clone()).header(reqwest::header::ACCEPT, "application/json");
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+        if !response.status().is_success() {
+            return Err(ProxyError::BadStatus { status: response.status().as_u16(), url: url.to_string() });
+        }
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let body = proxy::read_limited(response, proxy::MAX_RESPONSE_BYTES).await?;
+        Ok(Fetched::Body { body, etag })
+    }
+}
+
+enum Fetched {
+    NotModified,
+    Body { body: Vec<u8>, etag: Option<String> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, MockServer};
+    use tokio::net::TcpListener;
+
+    fn catalog_json(names: &[&str]) -> String {
+        let repos: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                format!(
+                    r#"{{"id":{},"name":"{}","description":"About {}","repositoryUrl":"https://github.com/o/{}","downloadCount":{},"uploadedByUsername":"u"}}"#,
+                    i, name, name, name, i * 10
+                )
+            })
+            .collect();
+        format!(r#"{{"success":true,"repositories":[{}]}}"#, repos.join(","))
+    }
+
+    fn temp_cache(name: &str) -> (PathBuf, CatalogCache) {
+        let dir = std::env::temp_dir().join(format!("ps-catalog-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = CatalogCache::for_install(&dir);
+        (dir, cache)
+    }
+
+    #[test]
+    fn cache_keys_differ_by_length() {
+        assert_eq!(cache_key(11), "top_l11");
+        assert_ne!(cache_key(11), cache_key(200));
+    }
+
+    #[tokio::test]
+    async fn parses_the_top_list_from_the_documented_endpoint() {
+        let server = MockServer::start(vec![response("200 OK", &[], &catalog_json(&["a", "b", "c"]))]).await;
+        let client = CatalogClient::new(&server.url).unwrap();
+
+        let page = client.top(None, Some(10), None).await.unwrap();
+        assert!(server.request(0).starts_with("GET /api/repositories/top?limit=11 HTTP/1.1"));
+        assert_eq!(page.repositories.len(), 3);
+        assert!(!page.has_more && !page.stale);
+        let first = &page.repositories[0];
+        assert_eq!(first.name, "a");
+        assert_eq!(first.repository_url.as_deref(), Some("https://github.com/o/a"));
+        assert_eq!(first.uploaded_by_username.as_deref(), Some("u"));
+    }
+
+    #[tokio::test]
+    async fn later_pages_and_search_are_cut_from_the_list() {
+        let names = ["alpha", "beta", "gamma", "delta", "epsilon"];
+        let server = MockServer::start(vec![
+            response("200 OK", &[], &catalog_json(&names)),
+            response("200 OK", &[], &catalog_json(&names)),
+        ])
+        .await;
+        let client = CatalogClient::new(&server.url).unwrap();
+
+        let page = client.top(None, Some(2), Some(2)).await.unwrap();
+        assert!(server.request(0).contains("limit=5 "));
+        assert_eq!(page.repositories.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), ["gamma", "delta"]);
+        assert!(page.has_more);
+
+        let found = client.search(None, "  EP", Some(10), None).await.unwrap();
+        assert!(server.request(1).contains(&format!("limit={} ", MAX_LISTED)));
+        assert_eq!(found.repositories.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), ["epsilon"]);
+        assert!(!found.has_more);
+    }
+
+    #[tokio::test]
+    async fn reports_server_failures() {
+        let server = MockServer::start(vec![
+            response("200 OK", &[], r#"{"success":false,"message":"maintenance"}"#),
+            response("200 OK", &[], "not json"),
+            response("404 Not Found", &[], ""),
+        ])
+        .await;
+        let client = CatalogClient::new(&server.url).unwrap();
+
+        let err = client.top(None, None, None).await.unwrap_err();
+        assert!(matches!(&err, ProxyError::InvalidResponse { message } if message == "maintenance"), "{}", err);
+        assert!(matches!(client.top(None, None, None).await, Err(ProxyError::InvalidResponse { .. })));
+        assert!(matches!(client.top(None, None, None).await, Err(ProxyError::BadStatus { status: 404, .. })));
+    }
+
+    #[tokio::test]
+    async fn revalidates_with_the_etag_and_falls_back_to_the_cache() {
+        let (dir, cache) = temp_cache("etag");
+        let server = MockServer::start(vec![
+            response("200 OK", &[("ETag", "\"v1\"")], &catalog_json(&["a"])),
+            response("304 Not Modified", &[], ""),
+            response("503 Service Unavailable", &[], ""),
+            response("404 Not Found", &[], ""),
+        ])
+        .await;
+        let client = CatalogClient::new(&server.url).unwrap();
+
+        let fresh = client.top(Some(&cache), None, None).await.unwrap();
+        assert!(!fresh.stale);
+        let revalidated = client.top(Some(&cache), None, None).await.unwrap();
+        assert!(server.request(1).to_lowercase().contains("if-none-match: \"v1\""));
+        assert_eq!(revalidated.repositories.len(), 1);
+        assert!(!revalidated.stale);
+
+        let offline = client.top(Some(&cache), None, None).await.unwrap();
+        assert!(offline.stale);
+        assert_eq!(offline.repositories[0].name, "a");
+        // Client errors are not hidden behind the cache
+        assert!(client.top(Some(&cache), None, None).await.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn a_cache_from_another_server_is_not_used() {
+        let (dir, cache) = temp_cache("other-server");
+        let server = MockServer::start(vec![response("200 OK", &[("ETag", "\"v1\"")], &catalog_json(&["a"]))]).await;
+        CatalogClient::new(&server.url).unwrap().top(Some(&cache), None, None).await.unwrap();
+
+        // Nothing listens on this port, so the request fails as if offline
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let unreachable = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let err = CatalogClient::new(&unreachable).unwrap().top(Some(&cache), None, None).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Network { .. }), "{}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
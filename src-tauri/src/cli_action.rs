@@ -0,0 +1,313 @@
+use std::fmt;
+use std::path::Path;
+
+use portablesource_rs::config::ConfigManager as PsConfigManager;
+use portablesource_rs::envs_manager::PortableEnvironmentManager as PsEnvManager;
+use portablesource_rs::repository_installer::RepositoryInstaller as PsRepoInstaller;
+
+use crate::console_settings::LogLevel;
+use crate::installed_repos;
+use crate::paths::{PathError, RepoName};
+
+// Typed form of the argument vector accepted by run_cli_command / run_cli_command_stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CliAction {
+    SetupEnv,
+    InstallRepo(String),
+    UpdateRepo(RepoName),
+    DeleteRepo(RepoName),
+    ListRepos,
+    CheckEnv,
+    Version,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CliParseError {
+    NoAction,
+    UnknownArgument(String),
+    MissingValue(&'static str),
+    ConflictingActions(String, String),
+    InvalidRepoName(PathError),
+}
+
+impl fmt::Display for CliParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliParseError::NoAction => write!(f, "No command given"),
+            CliParseError::UnknownArgument(arg) => write!(f, "Unknown argument '{}'", arg),
+            CliParseError::MissingValue(flag) => write!(f, "Missing repository name for {}", flag),
+            CliParseError::ConflictingActions(a, b) => write!(f, "Conflicting commands '{}' and '{}'", a, b),
+            CliParseError::InvalidRepoName(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl CliAction {
+    pub(crate) fn parse(args: &[String]) -> Result<Self, CliParseError> {
+        let mut action: Option<(CliAction, String)> = None;
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let parsed = match arg.as_str() {
+                "--setup-env" => CliAction::SetupEnv,
+                "--check-env" => CliAction::CheckEnv,
+                "--version" => CliAction::Version,
+                "list-repos" | "--list-repos" => CliAction::ListRepos,
+                "--install-repo" => CliAction::InstallRepo(repo_value(iter.next(), "--install-repo")?),
+                "--update-repo" => CliAction::UpdateRepo(repo_name(iter.next(), "--update-repo")?),
+                "--delete-repo" => CliAction::DeleteRepo(repo_name(iter.next(), "--delete-repo")?),
+                other => return Err(CliParseError::UnknownArgument(other.to_string())),
+            };
+
+            if let Some((_, previous)) = &action {
+                return Err(CliParseError::ConflictingActions(previous.clone(), arg.clone()));
+            }
+            action = Some((parsed, arg.clone()));
+        }
+
+        action.map(|(a, _)| a).ok_or(CliParseError::NoAction)
+    }
+
+    // Actions through which the library persists the config; these wait their turn per installation
+    pub(crate) fn writes_config(&self) -> bool {
+        matches!(self, CliAction::SetupEnv | CliAction::InstallRepo(_) | CliAction::UpdateRepo(_) | CliAction::DeleteRepo(_))
+    }
+}
+
+fn repo_value(value: Option<&String>, flag: &'static str) -> Result<String, CliParseError> {
+    match value {
+        Some(v) if !v.starts_with("--") && !v.trim().is_empty() => Ok(v.clone()),
+        _ => Err(CliParseError::MissingValue(flag)),
+    }
+}
+
+// Update/delete address an installed folder, so the value must be a plain repository name
+fn repo_name(value: Option<&String>, flag: &'static str) -> Result<RepoName, CliParseError> {
+    RepoName::parse(&repo_value(value, flag)?).map_err(CliParseError::InvalidRepoName)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
+        }
+    }
+}
+
+// Destination for dispatcher output: buffered into a CommandResult or streamed as events
+pub(crate) trait OutputSink {
+    fn line(&mut self, stream: OutputStream, data: &str);
+
+    // Milestones worth mirroring into the unified console; ignored by default
+    fn status(&mut self, _level: LogLevel, _module: &str, _message: &str) {}
+}
+
+// Collects output the way the non-streaming command returns it
+#[derive(Default)]
+pub(crate) struct BufferedSink {
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+}
+
+impl OutputSink for BufferedSink {
+    fn line(&mut self, stream: OutputStream, data: &str) {
+        let target = match stream {
+            OutputStream::Stdout => &mut self.stdout,
+            OutputStream::Stderr => &mut self.stderr,
+        };
+        target.push_str(data);
+        target.push('\n');
+    }
+}
+
+// Runs a parsed action against the library; returns true on success
+pub(crate) async fn dispatch(
+    action: &CliAction,
+    install_dir: &Path,
+    cfg: PsConfigManager,
+    sink: &mut (dyn OutputSink + Send),
+) -> bool {
+    match action {
+        CliAction::SetupEnv => {
+            sink.line(OutputStream::Stdout, "Setting up environment...");
+            sink.status(LogLevel::Info, "environment", "Starting environment setup");
+            let env_mgr = PsEnvManager::with_config(install_dir.to_path_buf(), cfg.clone());
+            match env_mgr.setup_environment().await {
+                Ok(_) => {
+                    sink.line(OutputStream::Stdout, "Environment setup completed successfully");
+                    sink.status(LogLevel::Info, "environment", "Environment setup completed successfully");
+                    true
+                }
+                Err(e) => {
+                    sink.line(OutputStream::Stderr, &e.to_string());
+                    sink.status(LogLevel::Error, "environment", &format!("Environment setup failed: {}", e));
+                    false
+                }
+            }
+        }
+        CliAction::InstallRepo(repo) => {
+            sink.line(OutputStream::Stdout, &format!("Installing repo '{}'...", repo));
+            sink.status(LogLevel::Info, "repository", &format!("Installing repository: {}", repo));
+            let mut installer = PsRepoInstaller::new(install_dir.to_path_buf(), cfg.clone());
+            match installer.install_repository(repo).await {
+                Ok(_) => {
+                    sink.line(OutputStream::Stdout, "Repository installed successfully");
+                    sink.status(LogLevel::Info, "repository", &format!("Repository '{}' installed successfully", repo));
+                    if let Err(e) = installed_repos::record_install(install_dir, repo) {
+                        sink.status(LogLevel::Warn, "repository", &e);
+                    }
+                    true
+                }
+                Err(e) => {
+                    sink.line(OutputStream::Stderr, &e.to_string());
+                    sink.status(LogLevel::Error, "repository", &format!("Repository installation failed: {}", e));
+                    false
+                }
+            }
+        }
+        CliAction::UpdateRepo(repo) => {
+            sink.line(OutputStream::Stdout, &format!("Updating repo '{}'...", repo));
+            let mut installer = PsRepoInstaller::new(install_dir.to_path_buf(), cfg.clone());
+            match installer.update_repository(repo.as_str()).await {
+                Ok(_) => {
+                    sink.line(OutputStream::Stdout, "Repository updated successfully");
+                    true
+                }
+                Err(e) => {
+                    sink.line(OutputStream::Stderr, &e.to_string());
+                    sink.status(LogLevel::Error, "repository", &format!("Repository update failed: {}", e));
+                    false
+                }
+            }
+        }
+        CliAction::DeleteRepo(repo) => {
+            sink.line(OutputStream::Stdout, &format!("Deleting repo '{}'...", repo));
+            let installer = PsRepoInstaller::new(install_dir.to_path_buf(), cfg.clone());
+            match installer.delete_repository(repo.as_str()) {
+                Ok(_) => {
+                    sink.line(OutputStream::Stdout, "Repository deleted successfully");
+                    if let Err(e) = installed_repos::forget(install_dir, repo.as_str()) {
+                        sink.status(LogLevel::Warn, "repository", &e);
+                    }
+                    true
+                }
+                Err(e) => {
+                    sink.line(OutputStream::Stderr, &e.to_string());
+                    false
+                }
+            }
+        }
+        CliAction::ListRepos => {
+            let installer = PsRepoInstaller::new(install_dir.to_path_buf(), cfg.clone());
+            match installer.list_repositories() {
+                Ok(repos) => {
+                    if repos.is_empty() {
+                        sink.line(OutputStream::Stdout, "No repositories installed");
+                    } else {
+                        sink.line(OutputStream::Stdout, "Installed repositories:");
+                        for repo in repos {
+                            sink.line(OutputStream::Stdout, &format!("  - {}", repo));
+                        }
+                    }
+                    true
+                }
+                Err(e) => {
+                    sink.line(OutputStream::Stderr, &e.to_string());
+                    false
+                }
+            }
+        }
+        CliAction::CheckEnv => {
+            let env_mgr = PsEnvManager::with_config(install_dir.to_path_buf(), cfg.clone());
+            match env_mgr.check_environment_status() {
+                Ok(status) => {
+                    sink.line(OutputStream::Stdout, &format!("Environment exists: {}", if status { "YES" } else { "NO" }));
+                    sink.line(OutputStream::Stdout, &format!("Setup completed: {}", if cfg.is_environment_setup_completed() { "YES" } else { "NO" }));
+                    true
+                }
+                Err(e) => {
+                    sink.line(OutputStream::Stderr, &e.to_string());
+                    false
+                }
+            }
+        }
+        CliAction::Version => {
+            sink.line(OutputStream::Stdout, &format!("PortableSource version: {}", portablesource_rs::config::VERSION));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliAction, CliParseError> {
+        CliAction::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parses_each_action() {
+        assert_eq!(parse(&["--setup-env"]), Ok(CliAction::SetupEnv));
+        assert_eq!(parse(&["--check-env"]), Ok(CliAction::CheckEnv));
+        assert_eq!(parse(&["--version"]), Ok(CliAction::Version));
+        assert_eq!(parse(&["list-repos"]), Ok(CliAction::ListRepos));
+        assert_eq!(parse(&["--list-repos"]), Ok(CliAction::ListRepos));
+        assert_eq!(
+            parse(&["--install-repo", "https://github.com/owner/repo"]),
+            Ok(CliAction::InstallRepo("https://github.com/owner/repo".to_string()))
+        );
+        assert_eq!(parse(&["--update-repo", "repo"]), Ok(CliAction::UpdateRepo(RepoName::parse("repo").unwrap())));
+        assert_eq!(parse(&["--delete-repo", "repo"]), Ok(CliAction::DeleteRepo(RepoName::parse("repo").unwrap())));
+    }
+
+    #[test]
+    fn rejects_unknown_arguments() {
+        assert_eq!(parse(&["--frobnicate"]), Err(CliParseError::UnknownArgument("--frobnicate".to_string())));
+        assert_eq!(parse(&["--setup-env", "extra"]), Err(CliParseError::UnknownArgument("extra".to_string())));
+        assert_eq!(parse(&[]), Err(CliParseError::NoAction));
+    }
+
+    #[test]
+    fn rejects_conflicting_actions() {
+        assert_eq!(
+            parse(&["--setup-env", "--check-env"]),
+            Err(CliParseError::ConflictingActions("--setup-env".to_string(), "--check-env".to_string()))
+        );
+        assert_eq!(
+            parse(&["--install-repo", "a", "--delete-repo", "b"]),
+            Err(CliParseError::ConflictingActions("--install-repo".to_string(), "--delete-repo".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(parse(&["--install-repo"]), Err(CliParseError::MissingValue("--install-repo")));
+        assert_eq!(parse(&["--install-repo", "  "]), Err(CliParseError::MissingValue("--install-repo")));
+        // A following flag is not taken as the value
+        assert_eq!(parse(&["--update-repo", "--setup-env"]), Err(CliParseError::MissingValue("--update-repo")));
+        assert_eq!(parse(&["--delete-repo"]), Err(CliParseError::MissingValue("--delete-repo")));
+    }
+
+    #[test]
+    fn update_and_delete_need_a_plain_repository_name() {
+        assert!(matches!(parse(&["--delete-repo", "../envs"]), Err(CliParseError::InvalidRepoName(_))));
+        assert!(matches!(parse(&["--update-repo", "owner/repo"]), Err(CliParseError::InvalidRepoName(_))));
+    }
+
+    #[test]
+    fn only_actions_that_persist_the_config_take_the_operation_lock() {
+        let writes: Vec<bool> = [&["--setup-env"][..], &["--install-repo", "a"], &["--list-repos"], &["--check-env"], &["--version"]]
+            .iter()
+            .map(|args| parse(args).unwrap().writes_config())
+            .collect();
+        assert_eq!(writes, [true, true, false, false, false]);
+    }
+}
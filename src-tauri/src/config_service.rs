@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use portablesource_rs::config::ConfigManager as PsConfigManager;
+use tokio::sync::OwnedMutexGuard;
+
+// Rereads that lost a race are retried this often before giving up
+const RELOAD_ATTEMPTS: usize = 3;
+
+// Disk is authoritative: the library persists its own changes while installs run, so memory is a
+// cache of the active installation's config file. Every change goes through this service and bumps
+// the version, which lets a caller that worked on a snapshot detect that someone else changed it.
+pub(crate) struct ConfigService {
+    current: Mutex<Stamped>,
+    // One per installation that ever ran a config-writing operation; there are only a handful
+    operations: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+// Held from handing a config to the library until its changes have been reloaded. The library
+// writes back the whole copy it was given, so two installs into one installation at the same
+// time would each overwrite what the other added; with this they take turns.
+pub(crate) struct OperationGuard {
+    _guard: OwnedMutexGuard<()>,
+}
+
+struct Stamped {
+    config: PsConfigManager,
+    version: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ConfigConflict {
+    pub(crate) expected: u64,
+    pub(crate) actual: u64,
+}
+
+impl fmt::Display for ConfigConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Config changed concurrently (expected version {}, found {})", self.expected, self.actual)
+    }
+}
+
+impl From<ConfigConflict> for String {
+    fn from(e: ConfigConflict) -> Self {
+        e.to_string()
+    }
+}
+
+// Loads an installation's config, pointed at it even if the file was written elsewhere
+pub(crate) fn load_for(install_dir: &Path) -> Result<PsConfigManager, String> {
+    let mut cfg = PsConfigManager::new(Some(install_dir.to_path_buf())).map_err(|e| e.to_string())?;
+    if cfg.get_config().install_path != install_dir {
+        cfg.set_install_path(install_dir.to_path_buf()).map_err(|e| e.to_string())?;
+    }
+    Ok(cfg)
+}
+
+impl ConfigService {
+    pub(crate) fn new(config: PsConfigManager) -> Self {
+        Self { current: Mutex::new(Stamped { config, version: 0 }), operations: Mutex::new(HashMap::new()) }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Stamped>, String> {
+        self.current.lock().map_err(|_| "Config state poisoned".to_string())
+    }
+
+    // A copy to hand to library calls, with the version it was taken at
+    pub(crate) fn snapshot(&self) -> Result<(PsConfigManager, u64), String> {
+        let current = self.lock()?;
+        Ok((current.config.clone(), current.version))
+    }
+
+    pub(crate) fn install_dir(&self) -> Option<PathBuf> {
+        let current = self.current.lock().ok()?;
+        let path = current.config.get_config().install_path.clone();
+        (!path.as_os_str().is_empty()).then_some(path)
+    }
+
+    // Atomic read-modify-write; the version only moves when `f` succeeds
+    pub(crate) fn update<T>(&self, f: impl FnOnce(&mut PsConfigManager) -> Result<T, String>) -> Result<T, String> {
+        let mut current = self.lock()?;
+        let mut config = current.config.clone();
+        let value = f(&mut config)?;
+        current.config = config;
+        current.version += 1;
+        Ok(value)
+    }
+
+    // Stores a config derived from the snapshot taken at `base`, unless something changed it since
+    pub(crate) fn commit(&self, base: u64, config: PsConfigManager) -> Result<u64, String> {
+        let mut current = self.lock()?;
+        if current.version != base {
+            return Err(ConfigConflict { expected: base, actual: current.version }.into());
+        }
+        current.config = config;
+        current.version += 1;
+        Ok(current.version)
+    }
+
+    // Config for an operation on `install_dir`: the active one (given this install path if it has
+    // none yet), or the other installation's own file without making it active
+    pub(crate) fn for_operation(&self, install_dir: &Path) -> Result<PsConfigManager, String> {
+        {
+            let current = self.lock()?;
+            let active = &current.config.get_config().install_path;
+            if active == install_dir {
+                return Ok(current.config.clone());
+            }
+            if !active.as_os_str().is_empty() {
+                return load_for(install_dir);
+            }
+        }
+        self.update(|cfg| {
+            if cfg.get_config().install_path.as_os_str().is_empty() {
+                cfg.set_install_path(install_dir.to_path_buf()).map_err(|e| e.to_string())?;
+            }
+            Ok(cfg.clone())
+        })
+    }
+
+    // for_operation for a library call that persists the config; waits for the previous one on the
+    // same installation to finish. Keep the guard until after the reload that follows the call.
+    pub(crate) async fn begin_operation(&self, install_dir: &Path) -> Result<(PsConfigManager, OperationGuard), String> {
+        let lock = {
+            let mut operations = self.operations.lock().map_err(|_| "Config state poisoned")?;
+            operations.entry(install_dir.to_path_buf()).or_default().clone()
+        };
+        let guard = OperationGuard { _guard: lock.lock_owned().await };
+        // Taken after the wait, so it includes what the previous operation wrote
+        Ok((self.for_operation(install_dir)?, guard))
+    }
+
+    // Rereads the file after an operation that may have written it. Skipped when another
+    // installation became active meanwhile, so a finishing job cannot undo a switch.
+    pub(crate) fn reload(&self, install_dir: &Path) -> Result<u64, String> {
+        for _ in 0..RELOAD_ATTEMPTS {
+            let (current, base) = self.snapshot()?;
+            let active = &current.get_config().install_path;
+            if !active.as_os_str().is_empty() && active != install_dir {
+                return Ok(base);
+            }
+            // Read outside the lock; a commit in between means this read may predate it
+            let loaded = load_for(install_dir)?;
+            match self.commit(base, loaded) {
+                Ok(version) => return Ok(version),
+                Err(e) => log::debug!("Retrying config reload: {}", e),
+            }
+        }
+        Err(format!("Config for {} kept changing while reloading", install_dir.display()))
+    }
+
+    // Makes another installation (or none) the active one
+    pub(crate) fn switch_to(&self, install_dir: Option<&Path>) -> Result<u64, String> {
+        let config = match install_dir {
+            Some(dir) => load_for(dir)?,
+            None => PsConfigManager::new(None).map_err(|e| e.to_string())?,
+        };
+        let mut current = self.lock()?;
+        current.config = config;
+        current.version += 1;
+        Ok(current.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Every test works on its own installation folder, never on the user's real config
+    fn temp_install(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ps-config-service-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn service(install_dir: &Path) -> ConfigService {
+        ConfigService::new(load_for(install_dir).unwrap())
+    }
+
+    #[test]
+    fn update_bumps_the_version_only_on_success() {
+        let dir = temp_install("update");
+        let service = service(&dir);
+        let (_, before) = service.snapshot().unwrap();
+        service.update(|_| Ok(())).unwrap();
+        let (_, after) = service.snapshot().unwrap();
+        assert_eq!(after, before + 1);
+
+        assert!(service.update(|_| Err::<(), _>("nope".to_string())).is_err());
+        assert_eq!(service.snapshot().unwrap().1, after);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn commit_rejects_a_stale_base() {
+        let dir = temp_install("commit");
+        let service = service(&dir);
+        let (config, base) = service.snapshot().unwrap();
+        service.update(|_| Ok(())).unwrap();
+
+        let err = service.commit(base, config.clone()).unwrap_err();
+        assert_eq!(err, ConfigConflict { expected: base, actual: base + 1 }.to_string());
+
+        let version = service.commit(base + 1, config).unwrap();
+        assert_eq!(version, base + 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn operations_on_one_installation_take_turns() {
+        let dir = temp_install("a");
+        let service = Arc::new(service(&dir));
+        let (_, first) = service.begin_operation(&dir).await.unwrap();
+
+        let waiting = {
+            let service = service.clone();
+            let dir = dir.clone();
+            tokio::spawn(async move { service.begin_operation(&dir).await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        // Another installation is not held up
+        let other = temp_install("b");
+        tokio::time::timeout(Duration::from_secs(1), service.begin_operation(&other)).await.unwrap().unwrap();
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&other);
+    }
+}
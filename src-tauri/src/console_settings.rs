@@ -0,0 +1,121 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+const MIN_MAX_ENTRIES: usize = 100;
+const MAX_MAX_ENTRIES: usize = 100_000;
+
+// Ordered by severity so a threshold is a plain comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    #[serde(alias = "trace")]
+    Debug,
+    Info,
+    #[serde(alias = "warning")]
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<log::Level> for LogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Trace | log::Level::Debug => LogLevel::Debug,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Warn => LogLevel::Warn,
+            log::Level::Error => LogLevel::Error,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" | "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            other => Err(format!("Unknown log level: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ConsoleSettings {
+    pub(crate) enabled: bool,
+    pub(crate) max_entries: usize,
+    pub(crate) log_level: LogLevel,
+}
+
+impl Default for ConsoleSettings {
+    fn default() -> Self {
+        Self { enabled: false, max_entries: DEFAULT_MAX_ENTRIES, log_level: LogLevel::Info }
+    }
+}
+
+impl ConsoleSettings {
+    // Keeps a hand-edited or UI-supplied capacity within sane bounds
+    pub(crate) fn normalized(mut self) -> Self {
+        self.max_entries = self.max_entries.clamp(MIN_MAX_ENTRIES, MAX_MAX_ENTRIES);
+        self
+    }
+
+    pub(crate) fn accepts(&self, level: LogLevel) -> bool {
+        level >= self.log_level
+    }
+}
+
+// Stored next to the installation so the settings travel with it
+fn settings_path(install_dir: &Path) -> PathBuf {
+    install_dir.join("settings").join("console.json")
+}
+
+// Defaults when the file is missing or unreadable
+pub(crate) fn load(install_dir: &Path) -> ConsoleSettings {
+    let path = settings_path(install_dir);
+    match std::fs::read(&path) {
+        Ok(raw) => match serde_json::from_slice::<ConsoleSettings>(&raw) {
+            Ok(settings) => settings.normalized(),
+            Err(e) => {
+                log::warn!("Ignoring invalid console settings {}: {}", path.display(), e);
+                ConsoleSettings::default()
+            }
+        },
+        Err(_) => ConsoleSettings::default(),
+    }
+}
+
+pub(crate) fn save(install_dir: &Path, settings: &ConsoleSettings) -> Result<(), String> {
+    let path = settings_path(install_dir);
+    let tmp = path.with_extension("json.tmp");
+    let raw = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&tmp, raw)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to save console settings to {}: {}", path.display(), e))
+}
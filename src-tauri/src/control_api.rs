@@ -0,0 +1,676 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{Ipv4Addr, TcpListener as StdTcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{EventId, Listener, Manager};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::jobs;
+use crate::AppState;
+
+const DEFAULT_PORT: u16 = 47631;
+const TOKEN_BYTES: usize = 32;
+// A restart on the same port waits this long for the previous listener task to let go
+const BIND_ATTEMPTS: u32 = 20;
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(25);
+
+// JSON-RPC 2.0 error codes; OPERATION_FAILED carries the command's own error message
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const OPERATION_FAILED: i64 = -32000;
+
+static NEXT_EVENT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ControlApiSettings {
+    pub(crate) enabled: bool,
+    pub(crate) port: u16,
+}
+
+impl Default for ControlApiSettings {
+    fn default() -> Self {
+        Self { enabled: false, port: DEFAULT_PORT }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ControlApiStatus {
+    pub(crate) settings: ControlApiSettings,
+    pub(crate) running: bool,
+    pub(crate) url: Option<String>,
+    pub(crate) token_path: Option<String>,
+    // Why the server is not running although enabled, e.g. the port is taken
+    pub(crate) error: Option<String>,
+}
+
+fn settings_path(install_dir: &Path) -> PathBuf {
+    install_dir.join("settings").join("control_api.json")
+}
+
+// Rewritten with a fresh token every time the server starts; clients read it from here
+fn token_path(install_dir: &Path) -> PathBuf {
+    install_dir.join("settings").join("control_api.token")
+}
+
+// Defaults (disabled) when the file is missing or unreadable
+pub(crate) fn load_settings(install_dir: &Path) -> ControlApiSettings {
+    let path = settings_path(install_dir);
+    match std::fs::read(&path) {
+        Ok(raw) => match serde_json::from_slice::<ControlApiSettings>(&raw) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("Ignoring invalid control API settings {}: {}", path.display(), e);
+                ControlApiSettings::default()
+            }
+        },
+        Err(_) => ControlApiSettings::default(),
+    }
+}
+
+fn write_atomic(path: &Path, raw: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&tmp, raw).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    // The token grants control over the installation; keep it from other local users
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600));
+    }
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub(crate) fn save_settings(install_dir: &Path, settings: &ControlApiSettings) -> Result<(), String> {
+    let raw = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    write_atomic(&settings_path(install_dir), &raw)
+}
+
+fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// Opt-in JSON-RPC 2.0 over WebSocket on 127.0.0.1 so other tools (a Stream Deck, home automation)
+// can drive the running app. Follows the active installation like the file watcher does.
+#[derive(Default)]
+pub(crate) struct ControlApi {
+    current: Mutex<Current>,
+}
+
+#[derive(Default)]
+struct Current {
+    install_dir: Option<PathBuf>,
+    settings: ControlApiSettings,
+    server: Option<Server>,
+    error: Option<String>,
+}
+
+struct Server {
+    port: u16,
+    token_path: PathBuf,
+    // Dropping it closes the listener and every open connection
+    _shutdown: watch::Sender<()>,
+}
+
+impl Current {
+    fn stop(&mut self) {
+        if let Some(server) = self.server.take() {
+            let _ = std::fs::remove_file(&server.token_path);
+        }
+    }
+
+    fn start_if_enabled(&mut self, app_handle: &tauri::AppHandle) {
+        self.error = None;
+        let Some(install_dir) = self.install_dir.clone().filter(|_| self.settings.enabled) else {
+            return;
+        };
+        match start(app_handle, &install_dir, self.settings.port) {
+            Ok(server) => {
+                log::info!("Control API listening on 127.0.0.1:{}", server.port);
+                self.server = Some(server);
+            }
+            Err(e) => {
+                log::warn!("Control API not started: {}", e);
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn status(&self) -> ControlApiStatus {
+        ControlApiStatus {
+            settings: self.settings.clone(),
+            running: self.server.is_some(),
+            url: self.server.as_ref().map(|s| format!("ws://127.0.0.1:{}", s.port)),
+            token_path: self.server.as_ref().map(|s| s.token_path.to_string_lossy().to_string()),
+            error: self.error.clone(),
+        }
+    }
+}
+
+impl ControlApi {
+    // Restarts the server for another installation (or stops it when there is none)
+    pub(crate) fn follow(&self, app_handle: &tauri::AppHandle, install_dir: Option<&Path>) {
+        let Ok(mut current) = self.current.lock() else {
+            return;
+        };
+        if current.install_dir.as_deref() == install_dir {
+            return;
+        }
+        current.stop();
+        current.install_dir = install_dir.map(Path::to_path_buf);
+        current.settings = install_dir.map(load_settings).unwrap_or_default();
+        current.start_if_enabled(app_handle);
+    }
+
+    pub(crate) fn status(&self) -> Result<ControlApiStatus, String> {
+        Ok(self.current.lock().map_err(|_| "Control API state poisoned")?.status())
+    }
+
+    // Saves the settings for the active installation and applies them; a restart issues a new token
+    pub(crate) fn configure(&self, app_handle: &tauri::AppHandle, settings: ControlApiSettings) -> Result<ControlApiStatus, String> {
+        let mut current = self.current.lock().map_err(|_| "Control API state poisoned")?;
+        let install_dir = current.install_dir.clone().ok_or("No active installation")?;
+        save_settings(&install_dir, &settings)?;
+        let unchanged = current.server.is_some() && settings.enabled && settings.port == current.settings.port;
+        if unchanged {
+            return Ok(current.status());
+        }
+        current.stop();
+        current.settings = settings;
+        current.start_if_enabled(app_handle);
+        Ok(current.status())
+    }
+}
+
+fn start(app_handle: &tauri::AppHandle, install_dir: &Path, port: u16) -> Result<Server, String> {
+    // Bound here rather than in the task so a taken port is reported to the caller
+    let listener = bind(port).map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+
+    let token = generate_token()?;
+    let token_path = token_path(install_dir);
+    write_atomic(&token_path, token.as_bytes())?;
+
+    let (shutdown, shutdown_rx) = watch::channel(());
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(serve(app_handle, listener, token, shutdown_rx));
+    Ok(Server { port, token_path, _shutdown: shutdown })
+}
+
+fn bind(port: u16) -> std::io::Result<StdTcpListener> {
+    let mut attempt = 1;
+    loop {
+        match StdTcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < BIND_ATTEMPTS => {
+                attempt += 1;
+                std::thread::sleep(BIND_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn serve(app_handle: tauri::AppHandle, listener: StdTcpListener, token: String, mut shutdown: watch::Receiver<()>) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::warn!("Control API listener failed: {}", e);
+            return;
+        }
+    };
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tauri::async_runtime::spawn(handle_connection(app_handle.clone(), stream, token.clone(), shutdown.clone()));
+                }
+                Err(e) => log::debug!("Control API accept failed: {}", e),
+            },
+            _ = shutdown.changed() => break,
+        }
+    }
+}
+
+// Constant-time so the comparison does not leak how much of a guess was right
+fn token_matches(candidate: &str, token: &str) -> bool {
+    candidate.len() == token.len() && candidate.bytes().zip(token.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// `Authorization: Bearer <token>`, or `?token=<token>` for clients that cannot set headers (browsers)
+fn request_token(request: &Request) -> Option<String> {
+    let header = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.trim().to_string());
+    header.or_else(|| {
+        request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix("token=")).map(|value| value.to_string())
+    })
+}
+
+async fn handle_connection(app_handle: tauri::AppHandle, stream: TcpStream, token: String, mut shutdown: watch::Receiver<()>) {
+    // The error type is fixed by tungstenite's handshake callback
+    #[allow(clippy::result_large_err)]
+    let authorize = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if request_token(request).map(|candidate| token_matches(&candidate, &token)).unwrap_or(false) {
+            return Ok(response);
+        }
+        let mut error = ErrorResponse::new(Some("Missing or invalid token".to_string()));
+        *error.status_mut() = StatusCode::UNAUTHORIZED;
+        Err(error)
+    };
+    let socket = match tokio_tungstenite::accept_hdr_async(stream, authorize).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::debug!("Control API handshake failed: {}", e);
+            return;
+        }
+    };
+
+    let (mut outgoing, mut incoming) = socket.split();
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    let mut connection = Connection { app_handle, events_tx, subscriptions: HashMap::new() };
+    loop {
+        let reply = tokio::select! {
+            message = incoming.next() => match message {
+                Some(Ok(Message::Text(text))) => connection.handle(text.as_str()).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by the socket itself
+                Some(Ok(_)) => None,
+            },
+            Some(event) = events_rx.recv() => Some(connection.notification(event)),
+            _ = shutdown.changed() => break,
+        };
+        if let Some(reply) = reply {
+            if outgoing.send(Message::text(reply)).await.is_err() {
+                break;
+            }
+        }
+    }
+    connection.close();
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    // Absent for notifications, which get no reply
+    id: Option<Value>,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        RpcError { code: OPERATION_FAILED, message }
+    }
+}
+
+fn error_reply(id: Value, error: RpcError) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": error.code, "message": error.message } }).to_string()
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError { code: INVALID_PARAMS, message: e.to_string() })
+}
+
+fn to_value<T: Serialize>(result: Result<T, String>) -> Result<Value, RpcError> {
+    serde_json::to_value(result?).map_err(|e| RpcError { code: INTERNAL_ERROR, message: e.to_string() })
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoParams {
+    repo: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoJobParams {
+    repo: String,
+    #[serde(default)]
+    ignore_disk_space: bool,
+    // Stream the job's events on this connection; on by default so none are missed
+    #[serde(default = "default_true")]
+    subscribe: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobParams {
+    job_id: String,
+}
+
+// One event of a job this connection subscribed to
+struct JobEvent {
+    event_id: String,
+    event: String,
+    payload: Value,
+}
+
+struct Subscription {
+    job_id: Option<String>,
+    finished_event: Option<String>,
+    listeners: Vec<EventId>,
+}
+
+struct Connection {
+    app_handle: tauri::AppHandle,
+    events_tx: mpsc::UnboundedSender<JobEvent>,
+    // Keyed by the job's event_id, which is known before the job id
+    subscriptions: HashMap<String, Subscription>,
+}
+
+// A request's method with its params, checked before anything runs
+#[derive(Debug)]
+enum Call {
+    InstallRepo(RepoJobParams),
+    UpdateRepo(RepoJobParams),
+    DeleteRepo(RepoParams),
+    ListRepos,
+    LaunchRepo(RepoParams),
+    StopRepo(RepoParams),
+    ListRunning,
+    GetJob(JobParams),
+    ListJobs,
+    CancelJob(JobParams),
+    Subscribe(JobParams),
+    Unsubscribe(JobParams),
+}
+
+impl Call {
+    fn parse(method: &str, raw: Value) -> Result<Self, RpcError> {
+        Ok(match method {
+            "install_repo" => Call::InstallRepo(params(raw)?),
+            "update_repo" => Call::UpdateRepo(params(raw)?),
+            "delete_repo" => Call::DeleteRepo(params(raw)?),
+            "list_repos" => Call::ListRepos,
+            "launch_repo" => Call::LaunchRepo(params(raw)?),
+            "stop_repo" => Call::StopRepo(params(raw)?),
+            "list_running" => Call::ListRunning,
+            "get_job" => Call::GetJob(params(raw)?),
+            "list_jobs" => Call::ListJobs,
+            "cancel_job" => Call::CancelJob(params(raw)?),
+            "subscribe" => Call::Subscribe(params(raw)?),
+            "unsubscribe" => Call::Unsubscribe(params(raw)?),
+            _ => return Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Unknown method '{}'", method) }),
+        })
+    }
+}
+
+// Parses one message, hands the call to `run` and builds the reply; notifications get none
+async fn respond<F, Fut>(text: &str, run: F) -> Option<String>
+where
+    F: FnOnce(Call) -> Fut,
+    Fut: Future<Output = Result<Value, RpcError>>,
+{
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return Some(error_reply(Value::Null, RpcError { code: PARSE_ERROR, message: e.to_string() })),
+    };
+    let request = match serde_json::from_value::<RpcRequest>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            let error = RpcError { code: INVALID_REQUEST, message: "Expected a JSON-RPC 2.0 request object".to_string() };
+            return Some(error_reply(Value::Null, error));
+        }
+    };
+    let result = match Call::parse(&request.method, request.params) {
+        Ok(call) => run(call).await,
+        Err(error) => Err(error),
+    };
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+        Err(error) => error_reply(id, error),
+    })
+}
+
+impl Connection {
+    async fn handle(&mut self, text: &str) -> Option<String> {
+        respond(text, |call| self.call(call)).await
+    }
+
+    async fn call(&mut self, call: Call) -> Result<Value, RpcError> {
+        let app_handle = self.app_handle.clone();
+        let state = app_handle.state::<AppState>();
+        let install_path = state.config.install_dir().ok_or_else(|| "No active installation".to_string())?.to_string_lossy().to_string();
+        match call {
+            Call::InstallRepo(p) => self.start_repo_job(install_path, "--install-repo", p).await,
+            Call::UpdateRepo(p) => self.start_repo_job(install_path, "--update-repo", p).await,
+            // Same per-path report as the GUI, so callers see folders that could not be deleted
+            Call::DeleteRepo(p) => to_value(crate::remove_repository_artifacts(state, install_path, p.repo).await),
+            Call::ListRepos => to_value(crate::list_installed_repositories(install_path).await),
+            Call::LaunchRepo(p) => to_value(crate::launch_repository(app_handle.clone(), state, install_path, p.repo).await),
+            Call::StopRepo(p) => to_value(crate::stop_repo(state, p.repo).await),
+            Call::ListRunning => to_value(crate::list_running_repos(state).await),
+            Call::GetJob(p) => to_value(crate::get_job(state, p.job_id).await),
+            Call::ListJobs => to_value(crate::list_jobs(state).await),
+            Call::CancelJob(p) => to_value(crate::cancel_job(state, p.job_id).await),
+            // Streams events of a job started elsewhere, e.g. from the GUI
+            Call::Subscribe(p) => {
+                let job = state.jobs.get(&p.job_id).ok_or_else(|| format!("Unknown job '{}'", p.job_id))?;
+                if !job.state.is_finished() && !self.subscriptions.contains_key(&job.event_id) {
+                    self.subscribe(&job.kind, &job.event_id);
+                    if let Some(subscription) = self.subscriptions.get_mut(&job.event_id) {
+                        subscription.job_id = Some(job.id.clone());
+                    }
+                }
+                to_value(Ok(job))
+            }
+            Call::Unsubscribe(p) => {
+                let event_id = self
+                    .subscriptions
+                    .iter()
+                    .find(|(_, s)| s.job_id.as_deref() == Some(p.job_id.as_str()))
+                    .map(|(event_id, _)| event_id.clone());
+                if let Some(event_id) = event_id {
+                    self.unsubscribe(&event_id);
+                }
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    async fn start_repo_job(&mut self, install_path: String, flag: &str, p: RepoJobParams) -> Result<Value, RpcError> {
+        let app_handle = self.app_handle.clone();
+        let state = app_handle.state::<AppState>();
+        let event_id = format!("rpc-{}", NEXT_EVENT_ID.fetch_add(1, Ordering::Relaxed));
+        // Listen before the job starts; its events queue up until this reply is sent
+        if p.subscribe {
+            self.subscribe("cli", &event_id);
+        }
+        let args = vec![flag.to_string(), p.repo];
+        match crate::run_cli_command_stream(app_handle.clone(), state, install_path, args, event_id.clone(), Some(p.ignore_disk_space)).await {
+            Ok(job_id) => {
+                if let Some(subscription) = self.subscriptions.get_mut(&event_id) {
+                    subscription.job_id = Some(job_id.clone());
+                }
+                Ok(json!({ "job_id": job_id, "event_id": event_id }))
+            }
+            Err(e) => {
+                self.unsubscribe(&event_id);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn subscribe(&mut self, kind: &str, event_id: &str) {
+        let listeners = jobs::event_names(kind, event_id)
+            .into_iter()
+            .map(|name| {
+                let tx = self.events_tx.clone();
+                let event_id = event_id.to_string();
+                let event = name.clone();
+                self.app_handle.listen_any(name, move |e| {
+                    let payload = serde_json::from_str(e.payload()).unwrap_or(Value::Null);
+                    let _ = tx.send(JobEvent { event_id: event_id.clone(), event: event.clone(), payload });
+                })
+            })
+            .collect();
+        let finished_event = jobs::finished_event(kind, event_id);
+        self.subscriptions.insert(event_id.to_string(), Subscription { job_id: None, finished_event, listeners });
+    }
+
+    fn unsubscribe(&mut self, event_id: &str) {
+        if let Some(subscription) = self.subscriptions.remove(event_id) {
+            for listener in subscription.listeners {
+                self.app_handle.unlisten(listener);
+            }
+        }
+    }
+
+    // job_event notification; the subscription ends with the job's finished event
+    fn notification(&mut self, event: JobEvent) -> String {
+        let subscription = self.subscriptions.get(&event.event_id);
+        let job_id = subscription.and_then(|s| s.job_id.clone());
+        let finished = subscription.and_then(|s| s.finished_event.as_deref()) == Some(event.event.as_str());
+        let name = event.event.strip_suffix(&format!("-{}", event.event_id)).unwrap_or(&event.event).to_string();
+        if finished {
+            self.unsubscribe(&event.event_id);
+        }
+        json!({
+            "jsonrpc": "2.0",
+            "method": "job_event",
+            "params": { "job_id": job_id, "event_id": event.event_id, "event": name, "payload": event.payload },
+        })
+        .to_string()
+    }
+
+    fn close(&mut self) {
+        let event_ids: Vec<String> = self.subscriptions.keys().cloned().collect();
+        for event_id in event_ids {
+            self.unsubscribe(&event_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::http;
+
+    fn request(uri: &str, authorization: Option<&str>) -> Request {
+        let mut builder = http::Request::builder().uri(uri);
+        if let Some(value) = authorization {
+            builder = builder.header("Authorization", value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(token_matches("s3cret", "s3cret"));
+        assert!(!token_matches("s3creT", "s3cret"));
+        assert!(!token_matches("s3cre", "s3cret"));
+        assert!(!token_matches("s3cret-and-more", "s3cret"));
+        assert!(!token_matches("", "s3cret"));
+    }
+
+    #[test]
+    fn token_comes_from_the_bearer_header() {
+        assert_eq!(request_token(&request("/", Some("Bearer abc "))).as_deref(), Some("abc"));
+        // The header wins over the query string
+        assert_eq!(request_token(&request("/?token=query", Some("Bearer header"))).as_deref(), Some("header"));
+    }
+
+    #[test]
+    fn token_falls_back_to_the_query_string() {
+        assert_eq!(request_token(&request("/?token=abc", None)).as_deref(), Some("abc"));
+        assert_eq!(request_token(&request("/?client=ui&token=abc", None)).as_deref(), Some("abc"));
+        assert_eq!(request_token(&request("/?token=abc", Some("Basic dXNlcjpwdw=="))).as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn no_token_without_header_or_parameter() {
+        assert_eq!(request_token(&request("/", None)), None);
+        assert_eq!(request_token(&request("/?xtoken=abc", None)), None);
+        assert_eq!(request_token(&request("/", Some("Basic dXNlcjpwdw=="))), None);
+    }
+
+    // Runs a message through Connection::handle's parsing with the dispatch stubbed out
+    async fn exchange(text: &str) -> (Option<Value>, Vec<String>) {
+        let mut calls = Vec::new();
+        let reply = respond(text, |call| {
+            calls.push(format!("{:?}", call));
+            async { Ok(json!("done")) }
+        })
+        .await;
+        (reply.map(|r| serde_json::from_str(&r).unwrap()), calls)
+    }
+
+    fn error_code(reply: &Option<Value>) -> Option<i64> {
+        reply.as_ref()?["error"]["code"].as_i64()
+    }
+
+    #[tokio::test]
+    async fn malformed_messages_are_rejected_before_dispatch() {
+        let (reply, calls) = exchange("{not json").await;
+        assert_eq!(error_code(&reply), Some(PARSE_ERROR));
+        assert_eq!(reply.unwrap()["id"], Value::Null);
+
+        let (reply, _) = exchange(r#"{"jsonrpc":"1.0","id":1,"method":"list_jobs"}"#).await;
+        assert_eq!(error_code(&reply), Some(INVALID_REQUEST));
+        assert!(calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_methods_and_bad_params_map_to_their_codes() {
+        let (reply, calls) = exchange(r#"{"jsonrpc":"2.0","id":7,"method":"format_disk"}"#).await;
+        assert_eq!(error_code(&reply), Some(METHOD_NOT_FOUND));
+        assert_eq!(reply.unwrap()["id"], 7);
+        assert!(calls.is_empty());
+
+        let (reply, calls) = exchange(r#"{"jsonrpc":"2.0","id":8,"method":"delete_repo","params":{"name":"x"}}"#).await;
+        assert_eq!(error_code(&reply), Some(INVALID_PARAMS));
+        assert!(calls.is_empty());
+
+        let (reply, _) = exchange(r#"{"jsonrpc":"2.0","id":9,"method":"get_job"}"#).await;
+        assert_eq!(error_code(&reply), Some(INVALID_PARAMS));
+    }
+
+    #[tokio::test]
+    async fn valid_calls_are_dispatched_and_answered() {
+        let (reply, calls) = exchange(r#"{"jsonrpc":"2.0","id":"a","method":"install_repo","params":{"repo":"facefusion"}}"#).await;
+        let reply = reply.unwrap();
+        assert_eq!(reply["id"], "a");
+        assert_eq!(reply["result"], "done");
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].starts_with("InstallRepo"), "{}", calls[0]);
+        assert!(calls[0].contains("subscribe: true"), "{}", calls[0]);
+
+        // Notifications run but get no reply
+        let (reply, calls) = exchange(r#"{"jsonrpc":"2.0","method":"list_jobs"}"#).await;
+        assert!(reply.is_none());
+        assert_eq!(calls, vec!["ListJobs".to_string()]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli_action::CliAction;
+use crate::install_path::{self, format_gib};
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DiskAction {
+    SetupEnv,
+    InstallRepo,
+    UpdateRepo,
+}
+
+impl DiskAction {
+    pub(crate) fn for_cli(action: &CliAction) -> Option<Self> {
+        match action {
+            CliAction::SetupEnv => Some(DiskAction::SetupEnv),
+            CliAction::InstallRepo(_) => Some(DiskAction::InstallRepo),
+            CliAction::UpdateRepo(_) => Some(DiskAction::UpdateRepo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DiskComponent {
+    Python,
+    Git,
+    Ffmpeg,
+    Cuda,
+    Repository,
+    RepositoryUpdate,
+}
+
+impl DiskComponent {
+    // Installed size plus the archive unpacked next to it, rounded up
+    fn estimated_bytes(self) -> u64 {
+        match self {
+            DiskComponent::Python => 300 * MIB,
+            DiskComponent::Git => 600 * MIB,
+            DiskComponent::Ffmpeg => 500 * MIB,
+            DiskComponent::Cuda => 8 * GIB,
+            // A torch-based repo: checkout, venv, CUDA wheels and the pip cache they pass through
+            DiskComponent::Repository => 15 * GIB,
+            DiskComponent::RepositoryUpdate => 3 * GIB,
+        }
+    }
+
+    // Folder under ps_env/ whose presence means setup_environment skips the tool
+    fn env_dir(self) -> Option<&'static str> {
+        match self {
+            DiskComponent::Python => Some("python"),
+            DiskComponent::Git => Some("git"),
+            DiskComponent::Ffmpeg => Some("ffmpeg"),
+            DiskComponent::Cuda => Some("CUDA"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ComponentEstimate {
+    pub(crate) component: DiskComponent,
+    pub(crate) bytes: u64,
+}
+
+// Result of check_disk_space; free_bytes is None when the volume cannot be queried
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SpacePreflight {
+    pub(crate) required_bytes: u64,
+    pub(crate) free_bytes: Option<u64>,
+    pub(crate) shortfall_bytes: u64,
+    pub(crate) sufficient: bool,
+    pub(crate) components: Vec<ComponentEstimate>,
+}
+
+impl SpacePreflight {
+    pub(crate) fn shortfall_message(&self) -> String {
+        format!(
+            "Not enough disk space: about {} needed, {} free ({} short). Free up space or override the disk space check",
+            format_gib(self.required_bytes),
+            format_gib(self.free_bytes.unwrap_or(0)),
+            format_gib(self.shortfall_bytes),
+        )
+    }
+}
+
+pub(crate) fn components_for(action: DiskAction, install_dir: &Path) -> Vec<DiskComponent> {
+    match action {
+        DiskAction::SetupEnv => missing_environment_components(install_dir),
+        DiskAction::InstallRepo => vec![DiskComponent::Repository],
+        DiskAction::UpdateRepo => vec![DiskComponent::RepositoryUpdate],
+    }
+}
+
+// Tools setup_environment would still download; CUDA only when an NVIDIA driver is present
+fn missing_environment_components(install_dir: &Path) -> Vec<DiskComponent> {
+    let ps_env = install_dir.join("ps_env");
+    let mut tools = vec![DiskComponent::Python, DiskComponent::Git, DiskComponent::Ffmpeg];
+    if has_nvidia_driver() {
+        tools.push(DiskComponent::Cuda);
+    }
+    tools.retain(|tool| {
+        tool.env_dir()
+            .map(|dir| !ps_env.join(dir).is_dir() && !ps_env.join(dir.to_lowercase()).is_dir())
+            .unwrap_or(true)
+    });
+    tools
+}
+
+pub(crate) fn has_nvidia_driver() -> bool {
+    let mut cmd = Command::new("nvidia-smi");
+    cmd.arg("-L").stdout(Stdio::null()).stderr(Stdio::null());
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000);
+    }
+    cmd.status().map(|s| s.success()).unwrap_or(false)
+}
+
+// Compares the estimate with the free space of the volume the install directory is (or will be) on
+pub(crate) fn preflight(install_dir: &Path, components: &[DiskComponent]) -> SpacePreflight {
+    let components: Vec<ComponentEstimate> =
+        components.iter().map(|&component| ComponentEstimate { component, bytes: component.estimated_bytes() }).collect();
+    let required_bytes = components.iter().map(|c| c.bytes).sum::<u64>();
+    let free_bytes = install_path::existing_ancestor(install_dir).and_then(|dir| install_path::volume_info(&dir).free_bytes);
+    // Unknown free space is not a reason to refuse
+    let shortfall_bytes = free_bytes.map(|free| required_bytes.saturating_sub(free)).unwrap_or(0);
+    SpacePreflight { required_bytes, free_bytes, shortfall_bytes, sufficient: shortfall_bytes == 0, components }
+}
@@ -0,0 +1,422 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::disk_space::{self, DiskComponent};
+
+// A healthy tool answers --version instantly; anything slower is hanging on something
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Tool {
+    Python,
+    Git,
+    Ffmpeg,
+    Cuda,
+}
+
+impl Tool {
+    pub(crate) const ALL: [Tool; 4] = [Tool::Python, Tool::Git, Tool::Ffmpeg, Tool::Cuda];
+
+    fn display_name(self) -> &'static str {
+        match self {
+            Tool::Python => "Python",
+            Tool::Git => "Git",
+            Tool::Ffmpeg => "FFmpeg",
+            Tool::Cuda => "CUDA",
+        }
+    }
+
+    // Same folder names disk_space checks to decide what setup_environment still downloads
+    fn env_dir_name(self) -> &'static str {
+        match self {
+            Tool::Python => "python",
+            Tool::Git => "git",
+            Tool::Ffmpeg => "ffmpeg",
+            Tool::Cuda => "CUDA",
+        }
+    }
+
+    // Where the executable sits inside the tool folder, most likely layout first
+    fn executable_candidates(self) -> &'static [&'static str] {
+        if cfg!(target_os = "windows") {
+            match self {
+                Tool::Python => &["python.exe"],
+                Tool::Git => &["cmd/git.exe", "bin/git.exe"],
+                Tool::Ffmpeg => &["ffmpeg.exe", "bin/ffmpeg.exe"],
+                Tool::Cuda => &["bin/nvcc.exe"],
+            }
+        } else {
+            match self {
+                Tool::Python => &["bin/python3", "bin/python"],
+                Tool::Git => &["bin/git"],
+                Tool::Ffmpeg => &["ffmpeg", "bin/ffmpeg"],
+                Tool::Cuda => &["bin/nvcc"],
+            }
+        }
+    }
+
+    pub(crate) fn disk_component(self) -> DiskComponent {
+        match self {
+            Tool::Python => DiskComponent::Python,
+            Tool::Git => DiskComponent::Git,
+            Tool::Ffmpeg => DiskComponent::Ffmpeg,
+            Tool::Cuda => DiskComponent::Cuda,
+        }
+    }
+
+    fn version_arg(self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "-version",
+            _ => "--version",
+        }
+    }
+
+    // CUDA is only installed (and only useful) on machines with an NVIDIA driver
+    fn is_required(self, has_nvidia: bool) -> bool {
+        self != Tool::Cuda || has_nvidia
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ToolStatus {
+    Ok,
+    Missing,
+    Broken,
+    NotNeeded,
+}
+
+impl ToolStatus {
+    fn needs_repair(self) -> bool {
+        matches!(self, ToolStatus::Missing | ToolStatus::Broken)
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ToolStatus::Ok => "working",
+            ToolStatus::Missing => "missing",
+            ToolStatus::Broken => "not starting",
+            ToolStatus::NotNeeded => "not needed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ToolDiagnosis {
+    pub(crate) tool: Tool,
+    pub(crate) expected_path: String,
+    pub(crate) exists: bool,
+    pub(crate) runs: bool,
+    // First line the tool printed for its version flag
+    pub(crate) version: Option<String>,
+    pub(crate) status: ToolStatus,
+    pub(crate) required: bool,
+    pub(crate) suggested_fix: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct EnvironmentDiagnosis {
+    pub(crate) install_path: String,
+    pub(crate) healthy: bool,
+    pub(crate) tools: Vec<ToolDiagnosis>,
+}
+
+// Checks every tool in ps_env/ by actually running it, not just by looking for its folder
+pub(crate) async fn diagnose(install_dir: &Path) -> EnvironmentDiagnosis {
+    let has_nvidia = tokio::task::spawn_blocking(disk_space::has_nvidia_driver).await.unwrap_or(false);
+    let mut tools = Vec::with_capacity(Tool::ALL.len());
+    for tool in Tool::ALL {
+        tools.push(diagnose_tool(install_dir, tool, tool.is_required(has_nvidia)).await);
+    }
+    let healthy = tools.iter().all(|t| matches!(t.status, ToolStatus::Ok | ToolStatus::NotNeeded));
+    EnvironmentDiagnosis { install_path: install_dir.to_string_lossy().to_string(), healthy, tools }
+}
+
+// The library creates the CUDA folder capitalized; older installs may have it in lowercase
+pub(crate) fn tool_dir(install_dir: &Path, tool: Tool) -> PathBuf {
+    let ps_env = install_dir.join("ps_env");
+    let name = tool.env_dir_name();
+    let lower = ps_env.join(name.to_lowercase());
+    if !ps_env.join(name).exists() && lower.exists() {
+        return lower;
+    }
+    ps_env.join(name)
+}
+
+fn executable_path(install_dir: &Path, tool: Tool) -> PathBuf {
+    let dir = tool_dir(install_dir, tool);
+    let candidates: Vec<PathBuf> = tool.executable_candidates().iter().map(|rel| dir.join(rel)).collect();
+    candidates.iter().find(|path| path.is_file()).unwrap_or(&candidates[0]).clone()
+}
+
+// The detected version is reported but not judged: the library does not say which versions it installs
+fn classify(exists: bool, runs: bool, required: bool) -> ToolStatus {
+    if !exists {
+        if required { ToolStatus::Missing } else { ToolStatus::NotNeeded }
+    } else if !runs {
+        ToolStatus::Broken
+    } else {
+        ToolStatus::Ok
+    }
+}
+
+async fn diagnose_tool(install_dir: &Path, tool: Tool, required: bool) -> ToolDiagnosis {
+    let executable = executable_path(install_dir, tool);
+    let exists = executable.is_file();
+    let probe = if exists { probe_version(&executable, tool.version_arg()).await } else { Err("not found".to_string()) };
+    let runs = probe.is_ok();
+    let version = probe.as_ref().ok().and_then(|output| version_line(tool, output));
+
+    let status = classify(exists, runs, required);
+    let suggested_fix = suggested_fix(tool, status, probe.err());
+
+    ToolDiagnosis {
+        tool,
+        expected_path: executable.to_string_lossy().to_string(),
+        exists,
+        runs,
+        version,
+        status,
+        required,
+        suggested_fix,
+    }
+}
+
+// Runs `<exe> <version flag>` and returns its output; a non-zero exit counts as not running
+async fn probe_version(executable: &Path, arg: &str) -> Result<String, String> {
+    let mut cmd = tokio::process::Command::new(executable);
+    cmd.arg(arg).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+    let output = match tokio::time::timeout(PROBE_TIMEOUT, cmd.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Err(format!("no answer within {} seconds", PROBE_TIMEOUT.as_secs())),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        let detail = stderr.lines().chain(stdout.lines()).map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+        return Err(format!("exited with {} {}", output.status, detail).trim_end().to_string());
+    }
+    // Python 2 printed its version to stderr
+    Ok(if stdout.trim().is_empty() { stderr } else { stdout })
+}
+
+// nvcc prints a banner first; its version is on the "Cuda compilation tools, release X.Y" line
+fn version_line(tool: Tool, output: &str) -> Option<String> {
+    let mut lines = output.lines().map(str::trim).filter(|l| !l.is_empty());
+    let line = match tool {
+        Tool::Cuda => lines.find(|l| l.contains("release")),
+        _ => lines.next(),
+    };
+    line.map(str::to_string)
+}
+
+fn suggested_fix(tool: Tool, status: ToolStatus, error: Option<String>) -> Option<String> {
+    let name = tool.display_name();
+    match status {
+        ToolStatus::Ok | ToolStatus::NotNeeded => None,
+        ToolStatus::Missing => Some(format!("Repair the environment to install {}", name)),
+        ToolStatus::Broken => Some(format!(
+            "{} does not start ({}). Repair the environment to reinstall it",
+            name,
+            error.unwrap_or_default()
+        )),
+    }
+}
+
+// Tools from `requested` (every tool when empty) that the diagnosis says have to be reinstalled.
+// Tools the machine does not need are left out: setup_environment would not install them again.
+pub(crate) fn repair_targets(diagnosis: &EnvironmentDiagnosis, requested: &[Tool]) -> Vec<Tool> {
+    diagnosis
+        .tools
+        .iter()
+        .filter(|t| requested.is_empty() || requested.contains(&t.tool))
+        .filter(|t| t.required && t.status.needs_repair())
+        .map(|t| t.tool)
+        .collect()
+}
+
+// Damaged tool folders moved out of the way while a repair reinstalls them. setup_environment only
+// installs a tool whose folder is missing, but nothing guarantees it does; until `commit` the old
+// folders are put back when the repair fails, is cancelled (the future is dropped) or panics.
+pub(crate) struct SetAside {
+    // (original folder, backup next to it)
+    moved: Vec<(PathBuf, PathBuf)>,
+    committed: bool,
+}
+
+impl SetAside {
+    pub(crate) fn move_aside(install_dir: &Path, tools: &[Tool]) -> Result<Self, String> {
+        let mut set_aside = SetAside { moved: Vec::new(), committed: false };
+        for tool in tools {
+            let dir = tool_dir(install_dir, *tool);
+            if !dir.exists() {
+                continue;
+            }
+            let backup = backup_dir(&dir);
+            // Left behind by a repair that was killed; the folder it belonged to is being reinstalled now
+            if backup.exists() {
+                std::fs::remove_dir_all(&backup).map_err(|e| format!("Failed to remove {}: {}", backup.display(), e))?;
+            }
+            // On error the folders moved so far are restored when `set_aside` drops
+            std::fs::rename(&dir, &backup).map_err(|e| format!("Failed to move {} aside: {}", dir.display(), e))?;
+            set_aside.moved.push((dir, backup));
+        }
+        Ok(set_aside)
+    }
+
+    // The reinstalled tools work: the old folders can go
+    pub(crate) fn commit(mut self) {
+        self.committed = true;
+        for (_, backup) in &self.moved {
+            if let Err(e) = std::fs::remove_dir_all(backup) {
+                log::warn!("Failed to remove {}: {}", backup.display(), e);
+            }
+        }
+    }
+}
+
+impl Drop for SetAside {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        for (dir, backup) in self.moved.iter().rev() {
+            // Whatever a failed or cancelled install left behind is not better than what was there
+            if dir.exists() {
+                if let Err(e) = std::fs::remove_dir_all(dir) {
+                    log::warn!("Failed to remove partial install {}: {}", dir.display(), e);
+                    continue;
+                }
+            }
+            match std::fs::rename(backup, dir) {
+                Ok(()) => log::info!("Restored {}", dir.display()),
+                Err(e) => log::warn!("Failed to restore {} from {}: {}", dir.display(), backup.display(), e),
+            }
+        }
+    }
+}
+
+// Next to the original so the rename stays on the same volume
+fn backup_dir(dir: &Path) -> PathBuf {
+    let name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    dir.with_file_name(format!("{}.repair-backup", name))
+}
+
+// Checks the tools a repair reinstalled; the error names each one that is still not working
+pub(crate) fn verify_repaired(diagnosis: &EnvironmentDiagnosis, targets: &[Tool]) -> Result<(), String> {
+    let failed: Vec<String> = diagnosis
+        .tools
+        .iter()
+        .filter(|t| targets.contains(&t.tool) && t.status != ToolStatus::Ok)
+        .map(|t| format!("{} is still {}", t.tool.display_name(), t.status.describe()))
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_install(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ps-env-doctor-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("ps_env").join("git")).unwrap();
+        std::fs::write(dir.join("ps_env").join("git").join("old"), "old").unwrap();
+        dir
+    }
+
+    #[test]
+    fn version_line_takes_the_first_line() {
+        assert_eq!(version_line(Tool::Python, "Python 3.11.9\n").as_deref(), Some("Python 3.11.9"));
+        assert_eq!(version_line(Tool::Git, "\n  git version 2.45.1.windows.1\n").as_deref(), Some("git version 2.45.1.windows.1"));
+        let ffmpeg = "ffmpeg version 7.0.1-essentials_build-www.gyan.dev Copyright (c) 2000-2024\nbuilt with gcc 13.2.0\n";
+        assert_eq!(version_line(Tool::Ffmpeg, ffmpeg).as_deref(), Some("ffmpeg version 7.0.1-essentials_build-www.gyan.dev Copyright (c) 2000-2024"));
+        assert_eq!(version_line(Tool::Python, "  \n\n"), None);
+    }
+
+    #[test]
+    fn version_line_finds_the_cuda_release() {
+        let nvcc = "nvcc: NVIDIA (R) Cuda compiler driver\nCopyright (c) 2005-2024 NVIDIA Corporation\nBuilt on Thu_Mar_28_02:30:10_Pacific_Daylight_Time_2024\nCuda compilation tools, release 12.4, V12.4.131\nBuild cuda_12.4.r12.4/compiler.34097967_0\n";
+        assert_eq!(version_line(Tool::Cuda, nvcc).as_deref(), Some("Cuda compilation tools, release 12.4, V12.4.131"));
+        assert_eq!(version_line(Tool::Cuda, "nvcc: NVIDIA (R) Cuda compiler driver\n"), None);
+    }
+
+    #[test]
+    fn classify_depends_on_existence_and_startup() {
+        assert_eq!(classify(true, true, true), ToolStatus::Ok);
+        assert_eq!(classify(true, true, false), ToolStatus::Ok);
+        assert_eq!(classify(true, false, true), ToolStatus::Broken);
+        assert_eq!(classify(false, false, true), ToolStatus::Missing);
+        assert_eq!(classify(false, false, false), ToolStatus::NotNeeded);
+    }
+
+    #[test]
+    fn repair_targets_skip_working_and_unneeded_tools() {
+        let tool = |tool, status, required| ToolDiagnosis {
+            tool,
+            expected_path: String::new(),
+            exists: status != ToolStatus::Missing,
+            runs: status == ToolStatus::Ok,
+            version: None,
+            status,
+            required,
+            suggested_fix: None,
+        };
+        let diagnosis = EnvironmentDiagnosis {
+            install_path: String::new(),
+            healthy: false,
+            tools: vec![
+                tool(Tool::Python, ToolStatus::Ok, true),
+                tool(Tool::Git, ToolStatus::Broken, true),
+                tool(Tool::Ffmpeg, ToolStatus::Missing, true),
+                tool(Tool::Cuda, ToolStatus::Broken, false),
+            ],
+        };
+        assert_eq!(repair_targets(&diagnosis, &[]), vec![Tool::Git, Tool::Ffmpeg]);
+        assert_eq!(repair_targets(&diagnosis, &[Tool::Ffmpeg, Tool::Python]), vec![Tool::Ffmpeg]);
+        assert!(repair_targets(&diagnosis, &[Tool::Cuda]).is_empty());
+    }
+
+    #[test]
+    fn set_aside_restores_the_old_folder_when_dropped() {
+        let install = temp_install("restore");
+        let git = install.join("ps_env").join("git");
+        {
+            let _set_aside = SetAside::move_aside(&install, &[Tool::Git, Tool::Python]).unwrap();
+            assert!(!git.exists());
+            // A half-finished reinstall
+            std::fs::create_dir_all(&git).unwrap();
+            std::fs::write(git.join("partial"), "new").unwrap();
+        }
+        assert!(git.join("old").exists());
+        assert!(!git.join("partial").exists());
+        assert!(!install.join("ps_env").join("git.repair-backup").exists());
+        let _ = std::fs::remove_dir_all(&install);
+    }
+
+    #[test]
+    fn set_aside_commit_keeps_the_reinstalled_folder() {
+        let install = temp_install("commit");
+        let git = install.join("ps_env").join("git");
+        let set_aside = SetAside::move_aside(&install, &[Tool::Git]).unwrap();
+        std::fs::create_dir_all(&git).unwrap();
+        std::fs::write(git.join("new"), "new").unwrap();
+        set_aside.commit();
+        assert!(git.join("new").exists());
+        assert!(!git.join("old").exists());
+        assert!(!install.join("ps_env").join("git.repair-backup").exists());
+        let _ = std::fs::remove_dir_all(&install);
+    }
+}
@@ -0,0 +1,320 @@
+use std::path::{Path, PathBuf};
+
+use portablesource_rs::utils as ps_utils;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::cli_action::{self, BufferedSink, CliAction, OutputSink, OutputStream};
+use crate::config_service;
+use crate::disk_space::{self, DiskAction};
+use crate::install_path;
+use crate::installations;
+use crate::installed_repos;
+use crate::paths::InstallRoot;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+// The command ran but the answer is no: environment not ready, path rejected, not enough disk space
+const EXIT_CHECK_FAILED: i32 = 3;
+
+const USAGE: &str = "\
+Usage: portablesource <command> [options]
+
+Commands:
+  setup-env                 Install python, git, ffmpeg and CUDA into ps_env
+  install-repo <name|url>   Install a repository
+  update-repo <name>        Update an installed repository
+  delete-repo <name>        Delete an installed repository
+  list-repos                List installed repositories
+  check-env                 Check the environment (exit code 3 when not ready)
+  validate-path <path>      Check a folder for a new installation (exit code 3 when rejected)
+  help                      Show this help
+
+Options:
+  --install-path <path>     Installation to use; defaults to the active one
+  --json                    Print one JSON object instead of text
+  --ignore-disk-space       Start downloads even when the disk space check fails
+
+Without a command the app window opens.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Cli(CliAction),
+    ValidatePath(String),
+    Help,
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    name: String,
+    command: Command,
+    install_path: Option<String>,
+    json: bool,
+    ignore_disk_space: bool,
+}
+
+// What --json prints; `data` is the structured result where a command has one
+#[derive(Debug, Serialize)]
+struct JsonOutput {
+    command: String,
+    success: bool,
+    exit_code: i32,
+    install_path: Option<String>,
+    stdout: String,
+    stderr: String,
+    data: Option<Value>,
+}
+
+// Prints dispatcher output as it comes, or keeps it for the JSON object
+struct Output {
+    json: bool,
+    buffer: BufferedSink,
+}
+
+impl OutputSink for Output {
+    fn line(&mut self, stream: OutputStream, data: &str) {
+        if self.json {
+            self.buffer.line(stream, data);
+            return;
+        }
+        match stream {
+            OutputStream::Stdout => println!("{}", data),
+            OutputStream::Stderr => eprintln!("{}", data),
+        }
+    }
+}
+
+impl Output {
+    fn finish(self, command: &str, exit_code: i32, install_dir: Option<&Path>, data: Option<Value>) -> i32 {
+        if self.json {
+            let output = JsonOutput {
+                command: command.to_string(),
+                success: exit_code == EXIT_OK,
+                exit_code,
+                install_path: install_dir.map(|dir| dir.to_string_lossy().to_string()),
+                stdout: self.buffer.stdout,
+                stderr: self.buffer.stderr,
+                data,
+            };
+            match serde_json::to_string_pretty(&output) {
+                Ok(raw) => println!("{}", raw),
+                Err(e) => eprintln!("Failed to serialize output: {}", e),
+            }
+        }
+        exit_code
+    }
+}
+
+fn is_command(arg: &str) -> bool {
+    matches!(
+        arg,
+        "setup-env" | "install-repo" | "update-repo" | "delete-repo" | "list-repos" | "check-env" | "validate-path" | "help" | "--help" | "-h"
+    )
+}
+
+// Entry point from main; only arguments that start with a command switch off the GUI
+pub(crate) fn run(args: &[String]) -> Option<i32> {
+    if !is_command(args.first()?) {
+        return None;
+    }
+    attach_console();
+    let code = match parse(args) {
+        Ok(options) => tauri::async_runtime::block_on(execute(options)),
+        Err(message) => {
+            let json = args.iter().any(|a| a == "--json");
+            let mut output = Output { json, buffer: BufferedSink::default() };
+            output.line(OutputStream::Stderr, &message);
+            if !json {
+                eprintln!("\n{}", USAGE);
+            }
+            output.finish(&args[0], EXIT_USAGE, None, None)
+        }
+    };
+    Some(code)
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+    let name = args[0].clone();
+    let mut install_path = None;
+    let mut json = false;
+    let mut ignore_disk_space = false;
+    let mut values = Vec::new();
+
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--ignore-disk-space" => ignore_disk_space = true,
+            "--install-path" => match iter.next() {
+                Some(path) if !path.starts_with("--") => install_path = Some(path.clone()),
+                _ => return Err("Missing path for --install-path".to_string()),
+            },
+            _ => values.push(arg.clone()),
+        }
+    }
+
+    // Everything except validate-path goes through the same parser as run_cli_command
+    let cli_flag = match name.as_str() {
+        "help" | "--help" | "-h" => None,
+        "validate-path" => None,
+        "list-repos" => Some("list-repos".to_string()),
+        other => Some(format!("--{}", other)),
+    };
+    let command = match cli_flag {
+        Some(flag) => {
+            let mut cli_args = vec![flag];
+            cli_args.extend(values);
+            Command::Cli(CliAction::parse(&cli_args).map_err(|e| e.to_string())?)
+        }
+        None if name == "validate-path" => match values.as_slice() {
+            [path] => Command::ValidatePath(path.clone()),
+            [] => return Err("Missing path for validate-path".to_string()),
+            [_, extra, ..] => return Err(format!("Unknown argument '{}'", extra)),
+        },
+        None => Command::Help,
+    };
+    Ok(Options { name, command, install_path, json, ignore_disk_space })
+}
+
+async fn execute(options: Options) -> i32 {
+    let mut output = Output { json: options.json, buffer: BufferedSink::default() };
+    match &options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            EXIT_OK
+        }
+        Command::ValidatePath(path) => validate_path(&options.name, path, output),
+        Command::Cli(action) => {
+            let install_dir = match resolve_install_dir(action, &options, &mut output) {
+                Ok(dir) => dir,
+                Err(code) => return output.finish(&options.name, code, None, None),
+            };
+            let mut code = run_action(action, &install_dir, &options, &mut output).await;
+            let data = match action {
+                CliAction::ListRepos if code == EXIT_OK => installed_repos::list(&install_dir).ok().and_then(|repos| serde_json::to_value(repos).ok()),
+                CliAction::CheckEnv if code == EXIT_OK => {
+                    let status = config_service::load_for(&install_dir).and_then(|cfg| crate::environment_status(&install_dir, cfg));
+                    if !status.as_ref().map(|s| s.setup_completed).unwrap_or(false) {
+                        code = EXIT_CHECK_FAILED;
+                    }
+                    status.ok().and_then(|status| serde_json::to_value(status).ok())
+                }
+                _ => None,
+            };
+            output.finish(&options.name, code, Some(&install_dir), data)
+        }
+    }
+}
+
+fn validate_path(command: &str, path: &str, mut output: Output) -> i32 {
+    let report = install_path::validate(path, dirs::download_dir().as_deref());
+    output.line(OutputStream::Stdout, &format!("Install path: {}", report.normalized_path));
+    for issue in &report.errors {
+        output.line(OutputStream::Stdout, &format!("  error: {}", issue.message));
+    }
+    for issue in &report.warnings {
+        output.line(OutputStream::Stdout, &format!("  warning: {}", issue.message));
+    }
+    output.line(OutputStream::Stdout, if report.valid { "Path can be used" } else { "Path cannot be used" });
+    let code = if report.valid { EXIT_OK } else { EXIT_CHECK_FAILED };
+    output.finish(command, code, None, serde_json::to_value(&report).ok())
+}
+
+// --install-path, else the installation the GUI has active, else the portable layout next to the binary.
+// setup-env treats --install-path like the GUI's folder picker: validated, with the leaf folder appended.
+fn resolve_install_dir(action: &CliAction, options: &Options, output: &mut Output) -> Result<PathBuf, i32> {
+    if let Some(path) = &options.install_path {
+        if *action == CliAction::SetupEnv {
+            let report = install_path::validate(path, dirs::download_dir().as_deref());
+            if !report.valid {
+                output.line(OutputStream::Stderr, &report.error_summary());
+                return Err(EXIT_CHECK_FAILED);
+            }
+            return Ok(install_path::normalize(path));
+        }
+        return InstallRoot::new(path).map(|root| root.path().to_path_buf()).map_err(|e| {
+            output.line(OutputStream::Stderr, &e.to_string());
+            EXIT_USAGE
+        });
+    }
+    let registry = installations::load_from(installations::headless_registry_path().as_deref());
+    registry.active().or_else(installations::exe_installation).ok_or_else(|| {
+        output.line(OutputStream::Stderr, "No installation found; pass --install-path");
+        EXIT_FAILED
+    })
+}
+
+async fn run_action(action: &CliAction, install_dir: &Path, options: &Options, output: &mut Output) -> i32 {
+    if let Some(disk_action) = DiskAction::for_cli(action) {
+        if let Err(code) = check_disk_space(install_dir, disk_action, options.ignore_disk_space, output) {
+            return code;
+        }
+    }
+    if *action == CliAction::SetupEnv {
+        if let Err(e) = prepare_install_dir(install_dir) {
+            output.line(OutputStream::Stderr, &e);
+            return EXIT_FAILED;
+        }
+    }
+    let cfg = match config_service::load_for(install_dir) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            output.line(OutputStream::Stderr, &format!("Failed to load config: {}", e));
+            return EXIT_FAILED;
+        }
+    };
+
+    if !cli_action::dispatch(action, install_dir, cfg, output).await {
+        return EXIT_FAILED;
+    }
+    if *action == CliAction::SetupEnv {
+        register_installation(install_dir, output);
+    }
+    EXIT_OK
+}
+
+fn check_disk_space(install_dir: &Path, action: DiskAction, ignore: bool, output: &mut Output) -> Result<(), i32> {
+    let components = disk_space::components_for(action, install_dir);
+    let preflight = disk_space::preflight(install_dir, &components);
+    if preflight.sufficient {
+        return Ok(());
+    }
+    if !ignore {
+        output.line(OutputStream::Stderr, &preflight.shortfall_message());
+        return Err(EXIT_CHECK_FAILED);
+    }
+    output.line(OutputStream::Stderr, &format!("Disk space check overridden: {}", preflight.shortfall_message()));
+    Ok(())
+}
+
+// Same preparation download_and_install_cli does before setting up the environment
+fn prepare_install_dir(install_dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(install_dir).map_err(|e| format!("Failed to create install directory: {}", e))?;
+    ps_utils::save_install_path_to_registry(install_dir).map_err(|e| e.to_string())?;
+    ps_utils::create_directory_structure(install_dir).map_err(|e| e.to_string())
+}
+
+// Makes a headless setup show up in the app; it only becomes active if nothing else is
+fn register_installation(install_dir: &Path, output: &mut Output) {
+    let Some(path) = installations::headless_registry_path() else {
+        return;
+    };
+    let mut registry = installations::load_from(Some(&path));
+    registry.add(install_dir, None);
+    if let Err(e) = installations::save_to(&path, &registry) {
+        output.line(OutputStream::Stderr, &format!("Warning: {}", e));
+    }
+}
+
+// Release builds use the GUI subsystem and start without a console; borrow the one we were started from
+#[cfg(target_os = "windows")]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn attach_console() {}
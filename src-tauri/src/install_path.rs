@@ -0,0 +1,375 @@
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+// Leaf folder every installation lives in
+pub(crate) const INSTALL_LEAF: &str = "portablesource";
+
+// Windows MAX_PATH; venvs and pip caches nest roughly this deep below the install root
+const MAX_PATH: usize = 260;
+const NESTED_DEPTH: usize = 160;
+const RECOMMENDED_PATH_LEN: usize = 40;
+
+// Below this nothing useful fits; below the recommendation a single large repo may not
+const MIN_FREE_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+const RECOMMENDED_FREE_BYTES: u64 = 30 * 1024 * 1024 * 1024;
+
+// Well-known names of the downloads folder, compared case-insensitively
+const DOWNLOAD_FOLDERS: &[&str] = &["downloads", "загрузки"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InstallPathIssue {
+    Empty,
+    NotAbsolute,
+    InDownloads,
+    DriveRoot,
+    NonAscii,
+    ContainsSpaces,
+    SpecialCharacters,
+    NotWritable,
+    PathTooLong,
+    LongPath,
+    InsufficientSpace,
+    LowSpace,
+    NetworkDrive,
+    RemovableDrive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PathIssue {
+    pub(crate) code: InstallPathIssue,
+    pub(crate) message: String,
+}
+
+// Result of validate_install_path; `errors` block installation, `warnings` only inform
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct InstallPathReport {
+    pub(crate) path: String,
+    pub(crate) normalized_path: String,
+    pub(crate) valid: bool,
+    pub(crate) errors: Vec<PathIssue>,
+    pub(crate) warnings: Vec<PathIssue>,
+    pub(crate) free_bytes: Option<u64>,
+}
+
+impl InstallPathReport {
+    fn error(&mut self, code: InstallPathIssue, message: impl Into<String>) {
+        self.valid = false;
+        self.errors.push(PathIssue { code, message: message.into() });
+    }
+
+    fn warning(&mut self, code: InstallPathIssue, message: impl Into<String>) {
+        self.warnings.push(PathIssue { code, message: message.into() });
+    }
+
+    // One line for commands that refuse the path
+    pub(crate) fn error_summary(&self) -> String {
+        let reasons: Vec<&str> = self.errors.iter().map(|e| e.message.as_str()).collect();
+        format!("Install path '{}' cannot be used: {}", self.path, reasons.join("; "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VolumeKind {
+    Local,
+    Network,
+    Removable,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct VolumeInfo {
+    pub(crate) free_bytes: Option<u64>,
+    pub(crate) kind: VolumeKind,
+}
+
+// Appends the portablesource leaf unless the path already ends with it
+pub(crate) fn normalize(path: &str) -> PathBuf {
+    let target = PathBuf::from(path.trim());
+    let has_leaf = target
+        .file_name()
+        .and_then(|s| s.to_str())
+        .map(|n| n.eq_ignore_ascii_case(INSTALL_LEAF))
+        .unwrap_or(false);
+    if has_leaf {
+        target
+    } else {
+        target.join(INSTALL_LEAF)
+    }
+}
+
+// Checks the README rules plus what the OS can tell about the target volume
+pub(crate) fn validate(path: &str, downloads_dir: Option<&Path>) -> InstallPathReport {
+    let raw = PathBuf::from(path.trim());
+    let target = normalize(path);
+    let display = target.to_string_lossy().to_string();
+    let mut report = InstallPathReport {
+        path: path.to_string(),
+        normalized_path: display.clone(),
+        valid: true,
+        errors: Vec::new(),
+        warnings: Vec::new(),
+        free_bytes: None,
+    };
+
+    if path.trim().is_empty() {
+        report.error(InstallPathIssue::Empty, "No folder selected");
+        return report;
+    }
+    if !raw.is_absolute() || raw.components().any(|c| matches!(c, Component::ParentDir | Component::CurDir)) {
+        report.error(InstallPathIssue::NotAbsolute, "Path must be a full path such as C:\\PortableSource");
+        return report;
+    }
+
+    if raw.parent().is_none() {
+        report.error(InstallPathIssue::DriveRoot, "Do not install onto the drive itself; pick a folder such as C:\\PortableSource");
+    }
+    if is_in_downloads(&target, downloads_dir) {
+        report.error(InstallPathIssue::InDownloads, "Do not install into the Downloads folder");
+    }
+    if !display.is_ascii() {
+        report.error(InstallPathIssue::NonAscii, "Path must contain English (ASCII) characters only");
+    }
+    if display.contains(' ') {
+        report.error(InstallPathIssue::ContainsSpaces, "Path must not contain spaces");
+    }
+    if let Some(c) = display.chars().find(|c| c.is_ascii() && !is_allowed_char(*c)) {
+        report.error(InstallPathIssue::SpecialCharacters, format!("Path must not contain special symbols such as '{}'", c));
+    }
+
+    // Only Windows has a path limit the nested envs/ and repos/ trees can run into
+    if cfg!(target_os = "windows") {
+        let len = display.chars().count();
+        if len + NESTED_DEPTH > MAX_PATH {
+            report.error(
+                InstallPathIssue::PathTooLong,
+                format!("Path is {} characters long; keep it under {} so nested packages stay below {}", len, MAX_PATH - NESTED_DEPTH, MAX_PATH),
+            );
+        } else if len > RECOMMENDED_PATH_LEN {
+            report.warning(InstallPathIssue::LongPath, format!("Path is {} characters long; a short path near the drive root is safer", len));
+        }
+    }
+
+    let Some(existing) = existing_ancestor(&target) else {
+        report.error(InstallPathIssue::NotWritable, "No part of the path exists");
+        return report;
+    };
+    if let Err(e) = probe_writable(&existing) {
+        report.error(InstallPathIssue::NotWritable, format!("Cannot write to {}: {}", existing.display(), e));
+    }
+
+    let volume = volume_info(&existing);
+    report.free_bytes = volume.free_bytes;
+    if let Some(free) = volume.free_bytes {
+        if free < MIN_FREE_BYTES {
+            report.error(
+                InstallPathIssue::InsufficientSpace,
+                format!("Only {} free; at least {} is required", format_gib(free), format_gib(MIN_FREE_BYTES)),
+            );
+        } else if free < RECOMMENDED_FREE_BYTES {
+            report.warning(
+                InstallPathIssue::LowSpace,
+                format!("Only {} free; {} or more is recommended for models and packages", format_gib(free), format_gib(RECOMMENDED_FREE_BYTES)),
+            );
+        }
+    }
+    match volume.kind {
+        // Virtual environments and git break in odd ways on network shares
+        VolumeKind::Network => report.error(InstallPathIssue::NetworkDrive, "Network drives are not supported; pick a local disk"),
+        VolumeKind::Removable => {
+            report.warning(InstallPathIssue::RemovableDrive, "Removable drive: installs are slow and stop working while it is unplugged")
+        }
+        VolumeKind::Local => {}
+    }
+
+    report
+}
+
+fn is_allowed_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | '\\' | ':' | ' ')
+}
+
+fn is_in_downloads(target: &Path, downloads_dir: Option<&Path>) -> bool {
+    if let Some(downloads) = downloads_dir {
+        let lower = |p: &Path| p.to_string_lossy().to_lowercase();
+        if Path::new(&lower(target)).starts_with(lower(downloads)) {
+            return true;
+        }
+    }
+    target.components().any(|c| match c {
+        Component::Normal(name) => name.to_str().map(|n| DOWNLOAD_FOLDERS.contains(&n.to_lowercase().as_str())).unwrap_or(false),
+        _ => false,
+    })
+}
+
+pub(crate) fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|p| p.is_dir()).map(|p| p.to_path_buf())
+}
+
+// A directory rather than a file: standard users may create folders in C:\ but not files
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".ps-write-test-{}", std::process::id()));
+    std::fs::create_dir(&probe)?;
+    std::fs::remove_dir(&probe)
+}
+
+pub(crate) fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn volume_info(path: &Path) -> VolumeInfo {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{GetDiskFreeSpaceExW, GetDriveTypeW, GetVolumePathNameW};
+
+    const DRIVE_REMOVABLE: u32 = 2;
+    const DRIVE_REMOTE: u32 = 4;
+    const DRIVE_CDROM: u32 = 5;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut free: u64 = 0;
+    // SAFETY: `wide` is NUL-terminated and the out pointers are valid for the call
+    let free_bytes = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut free, std::ptr::null_mut(), std::ptr::null_mut()) != 0 }
+        .then_some(free);
+
+    let mut root = [0u16; 261];
+    // SAFETY: the buffer length passed matches `root`
+    let kind = if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } != 0 {
+        match unsafe { GetDriveTypeW(root.as_ptr()) } {
+            DRIVE_REMOTE => VolumeKind::Network,
+            DRIVE_REMOVABLE | DRIVE_CDROM => VolumeKind::Removable,
+            _ => VolumeKind::Local,
+        }
+    } else if path.to_string_lossy().starts_with("\\\\") && !path.to_string_lossy().starts_with("\\\\?\\") {
+        VolumeKind::Network
+    } else {
+        VolumeKind::Local
+    };
+    VolumeInfo { free_bytes, kind }
+}
+
+#[cfg(unix)]
+pub(crate) fn volume_info(path: &Path) -> VolumeInfo {
+    use std::os::unix::ffi::OsStrExt;
+
+    let free_bytes = std::ffi::CString::new(path.as_os_str().as_bytes()).ok().and_then(|c_path| {
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer
+        (unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } == 0).then(|| stat.f_bavail as u64 * stat.f_frsize as u64)
+    });
+    VolumeInfo { free_bytes, kind: mount_kind(path) }
+}
+
+// Filesystem type of the longest mount point containing `path`, from /proc/self/mounts
+#[cfg(target_os = "linux")]
+fn mount_kind(path: &Path) -> VolumeKind {
+    const NETWORK_FS: &[&str] = &["nfs", "nfs4", "cifs", "smb3", "smbfs", "fuse.sshfs", "9p", "afs", "ceph", "glusterfs"];
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else { return VolumeKind::Local };
+    let best = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // Spaces in mount points are escaped as \040
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?.to_string();
+            Some((mount_point, fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len());
+    match best {
+        Some((_, fs_type)) if NETWORK_FS.contains(&fs_type.as_str()) => VolumeKind::Network,
+        Some((mount_point, _)) if mount_point.starts_with("/media/") || mount_point.starts_with("/run/media/") => VolumeKind::Removable,
+        _ => VolumeKind::Local,
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn mount_kind(path: &Path) -> VolumeKind {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else { return VolumeKind::Local };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return VolumeKind::Local;
+    }
+    if (stat.f_flags as u64) & (libc::MNT_LOCAL as u64) == 0 {
+        VolumeKind::Network
+    } else if path.starts_with("/Volumes") {
+        VolumeKind::Removable
+    } else {
+        VolumeKind::Local
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(report: &InstallPathReport) -> Vec<InstallPathIssue> {
+        report.errors.iter().map(|e| e.code).collect()
+    }
+
+    // Free space depends on the machine running the tests, so those checks are left out
+    fn path_errors(report: &InstallPathReport) -> Vec<InstallPathIssue> {
+        errors(report).into_iter().filter(|c| *c != InstallPathIssue::InsufficientSpace).collect()
+    }
+
+    fn under_temp(rest: &str) -> String {
+        std::env::temp_dir().join(rest).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn normalize_appends_the_leaf_once() {
+        let base = std::env::temp_dir();
+        assert_eq!(normalize(&base.to_string_lossy()), base.join(INSTALL_LEAF));
+        let with_leaf = base.join("PortableSource");
+        assert_eq!(normalize(&format!("  {}  ", with_leaf.display())), with_leaf);
+    }
+
+    #[test]
+    fn empty_and_relative_paths_are_rejected_first() {
+        assert_eq!(errors(&validate("   ", None)), [InstallPathIssue::Empty]);
+        assert_eq!(errors(&validate("portable", None)), [InstallPathIssue::NotAbsolute]);
+        assert_eq!(errors(&validate(&under_temp("a/../b"), None)), [InstallPathIssue::NotAbsolute]);
+    }
+
+    #[test]
+    fn a_plain_folder_in_a_writable_place_is_valid() {
+        let report = validate(&under_temp("ps_install-1.0"), None);
+        assert!(path_errors(&report).is_empty(), "{:?}", report.errors);
+        assert!(report.normalized_path.ends_with(INSTALL_LEAF));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn the_filesystem_root_is_rejected() {
+        assert!(errors(&validate("/", None)).contains(&InstallPathIssue::DriveRoot));
+    }
+
+    #[test]
+    fn downloads_folders_are_rejected() {
+        assert!(errors(&validate(&under_temp("Downloads/ps"), None)).contains(&InstallPathIssue::InDownloads));
+        assert!(errors(&validate(&under_temp("Загрузки/ps"), None)).contains(&InstallPathIssue::InDownloads));
+        let custom = std::env::temp_dir().join("incoming");
+        assert!(errors(&validate(&under_temp("INCOMING/ps"), Some(&custom))).contains(&InstallPathIssue::InDownloads));
+        assert!(!errors(&validate(&under_temp("elsewhere"), Some(&custom))).contains(&InstallPathIssue::InDownloads));
+    }
+
+    #[test]
+    fn unusual_characters_are_rejected() {
+        assert!(errors(&validate(&under_temp("программы"), None)).contains(&InstallPathIssue::NonAscii));
+        assert!(errors(&validate(&under_temp("my apps"), None)).contains(&InstallPathIssue::ContainsSpaces));
+        let special = validate(&under_temp("apps&tools"), None);
+        assert!(errors(&special).contains(&InstallPathIssue::SpecialCharacters));
+        assert!(special.error_summary().contains("'&'"));
+    }
+
+    #[test]
+    fn existing_ancestor_skips_missing_folders() {
+        let base = std::env::temp_dir();
+        assert_eq!(existing_ancestor(&base.join("ps-missing").join("deeper")), Some(base));
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::AppState;
+
+// A CLI install rewrites the config many times in a row; one reload per burst is enough
+const DEBOUNCE: Duration = Duration::from_millis(750);
+// Folders whose direct children are repositories
+const REPO_DIRS: [&str; 2] = ["repos", "envs"];
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ConfigChanged {
+    pub(crate) install_path: String,
+    pub(crate) version: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ReposChanged {
+    pub(crate) install_path: String,
+}
+
+// Watches the active installation for changes made outside the app, e.g. by the CLI in a terminal.
+// The config file lives at the top of the install dir; repos/ and envs/ are watched one level deep,
+// so an install writing thousands of files into repos/<name> does not flood the handler.
+#[derive(Default)]
+pub(crate) struct InstallWatcher {
+    current: Mutex<Option<Watched>>,
+}
+
+struct Watched {
+    install_dir: PathBuf,
+    debouncer: Debouncer<RecommendedWatcher>,
+    // repos/ and envs/ only exist once setup created them
+    repo_dirs: Vec<PathBuf>,
+}
+
+impl Watched {
+    // Starts watching repo folders that appeared and forgets ones that were removed
+    fn refresh_repo_dirs(&mut self) {
+        for name in REPO_DIRS {
+            let dir = self.install_dir.join(name);
+            let watched = self.repo_dirs.contains(&dir);
+            if dir.is_dir() && !watched {
+                match self.debouncer.watcher().watch(&dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => self.repo_dirs.push(dir),
+                    Err(e) => log::warn!("Failed to watch {}: {}", dir.display(), e),
+                }
+            } else if !dir.is_dir() && watched {
+                let _ = self.debouncer.watcher().unwatch(&dir);
+                self.repo_dirs.retain(|d| d != &dir);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Changes {
+    config: bool,
+    repos: bool,
+    // repos/ or envs/ itself was created or removed
+    repo_dirs: bool,
+}
+
+fn classify<'a>(install_dir: &Path, paths: impl Iterator<Item = &'a Path>) -> Changes {
+    let mut changes = Changes::default();
+    for path in paths {
+        let parent = path.parent();
+        if REPO_DIRS.iter().any(|name| path == install_dir.join(name)) {
+            changes.repo_dirs = true;
+            changes.repos = true;
+        } else if REPO_DIRS.iter().any(|name| parent == Some(install_dir.join(name).as_path())) {
+            changes.repos = true;
+        } else if parent == Some(install_dir) && path.extension().map(|ext| ext == "json").unwrap_or(false) {
+            // Temp files written before a rename end in .tmp and are skipped
+            changes.config = true;
+        }
+    }
+    changes
+}
+
+impl InstallWatcher {
+    // Moves the watch to `install_dir`, or stops watching when there is no active installation
+    pub(crate) fn follow(&self, app_handle: &tauri::AppHandle, install_dir: Option<&Path>) -> Result<(), String> {
+        let mut current = self.current.lock().map_err(|_| "Watcher state poisoned")?;
+        if current.as_ref().map(|w| w.install_dir.as_path()) == install_dir {
+            return Ok(());
+        }
+        // Dropping the debouncer stops its thread and releases the directory handles
+        *current = None;
+        let Some(install_dir) = install_dir else {
+            return Ok(());
+        };
+
+        let handler = {
+            let app_handle = app_handle.clone();
+            let install_dir = install_dir.to_path_buf();
+            move |result: DebounceEventResult| handle_events(&app_handle, &install_dir, result)
+        };
+        let mut debouncer = new_debouncer(DEBOUNCE, handler).map_err(|e| format!("Failed to start file watcher: {}", e))?;
+        debouncer
+            .watcher()
+            .watch(install_dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", install_dir.display(), e))?;
+        let mut watched = Watched { install_dir: install_dir.to_path_buf(), debouncer, repo_dirs: Vec::new() };
+        watched.refresh_repo_dirs();
+        *current = Some(watched);
+        Ok(())
+    }
+
+    fn refresh_repo_dirs(&self, install_dir: &Path) {
+        if let Ok(mut current) = self.current.lock() {
+            // Events of a watcher that was just replaced are for the old installation
+            if let Some(watched) = current.as_mut().filter(|w| w.install_dir == install_dir) {
+                watched.refresh_repo_dirs();
+            }
+        }
+    }
+}
+
+// Runs on the debouncer thread once a burst of changes settled
+fn handle_events(app_handle: &tauri::AppHandle, install_dir: &Path, result: DebounceEventResult) {
+    let events = match result {
+        Ok(events) => events,
+        Err(e) => {
+            log::warn!("File watcher error: {}", e);
+            return;
+        }
+    };
+    let changes = classify(install_dir, events.iter().map(|e| e.path.as_path()));
+    let state = app_handle.state::<AppState>();
+    let install_path = install_dir.to_string_lossy().to_string();
+
+    if changes.repo_dirs {
+        state.install_watcher.refresh_repo_dirs(install_dir);
+    }
+    if changes.config {
+        match state.config.reload(install_dir) {
+            Ok(version) => {
+                log::debug!("Config changed on disk, reloaded as version {}", version);
+                let _ = app_handle.emit("config-changed", ConfigChanged { install_path: install_path.clone(), version });
+            }
+            Err(e) => log::warn!("Failed to reload config after change on disk: {}", e),
+        }
+    }
+    if changes.repos {
+        let _ = app_handle.emit("repos-changed", ReposChanged { install_path });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify_paths(install_dir: &Path, paths: &[PathBuf]) -> Changes {
+        classify(install_dir, paths.iter().map(PathBuf::as_path))
+    }
+
+    #[test]
+    fn config_files_at_the_top_are_config_changes() {
+        let install = PathBuf::from("/ps");
+        let changes = classify_paths(&install, &[install.join("portablesource_config.json")]);
+        assert!(changes.config && !changes.repos && !changes.repo_dirs);
+
+        // Temp files, other extensions and nested json files are not the config
+        let ignored = [install.join("config.json.tmp"), install.join("notes.txt"), install.join("settings").join("console.json")];
+        let changes = classify_paths(&install, &ignored);
+        assert!(!changes.config && !changes.repos && !changes.repo_dirs);
+    }
+
+    #[test]
+    fn repository_folders_are_repo_changes() {
+        let install = PathBuf::from("/ps");
+        let changes = classify_paths(&install, &[install.join("repos").join("ComfyUI"), install.join("envs").join("ComfyUI")]);
+        assert!(changes.repos && !changes.repo_dirs && !changes.config);
+
+        // Files deeper inside a repository are not reported on their own
+        let changes = classify_paths(&install, &[install.join("repos").join("ComfyUI").join("main.py")]);
+        assert!(!changes.repos);
+    }
+
+    #[test]
+    fn the_repo_folders_themselves_also_count_as_repo_changes() {
+        let install = PathBuf::from("/ps");
+        let changes = classify_paths(&install, &[install.join("envs")]);
+        assert!(changes.repo_dirs && changes.repos && !changes.config);
+    }
+
+    #[test]
+    fn other_installations_are_ignored() {
+        let install = PathBuf::from("/ps");
+        let other = PathBuf::from("/other");
+        let changes = classify_paths(&install, &[other.join("config.json"), other.join("repos").join("x"), other.join("repos")]);
+        assert!(!changes.config && !changes.repos && !changes.repo_dirs);
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+const REGISTRY_FILE: &str = "installations.json";
+// `identifier` from tauri.conf.json; app_config_dir is <config dir>/<identifier>
+const APP_IDENTIFIER: &str = "dev.portablesource";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Installation {
+    pub(crate) path: PathBuf,
+    pub(crate) label: Option<String>,
+    pub(crate) added_at: DateTime<Utc>,
+}
+
+// Known installations, persisted per user so several installs (e.g. SSD and HDD) can be managed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct InstallationRegistry {
+    pub(crate) active: Option<PathBuf>,
+    pub(crate) installations: Vec<Installation>,
+}
+
+// What list_installations returns for each entry
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct InstallationInfo {
+    pub(crate) path: String,
+    pub(crate) label: Option<String>,
+    pub(crate) added_at: DateTime<Utc>,
+    pub(crate) active: bool,
+    // ps_env is present; false for installs on an unplugged drive or deleted by hand
+    pub(crate) available: bool,
+}
+
+// Windows paths compare case-insensitively, and a trailing separator does not make a new install
+pub(crate) fn same_path(a: &Path, b: &Path) -> bool {
+    let key = |p: &Path| {
+        let s = p.to_string_lossy();
+        let s = s.trim_end_matches(['/', '\\']);
+        if cfg!(target_os = "windows") {
+            s.replace('/', "\\").to_lowercase()
+        } else {
+            s.to_string()
+        }
+    };
+    key(a) == key(b)
+}
+
+pub(crate) fn is_installation(dir: &Path) -> bool {
+    dir.join("ps_env").is_dir()
+}
+
+// The portable layout: the app sits next to ps_env
+pub(crate) fn exe_installation() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let exe_dir = exe.parent()?;
+    is_installation(exe_dir).then(|| exe_dir.to_path_buf())
+}
+
+impl InstallationRegistry {
+    fn find(&self, path: &Path) -> Option<usize> {
+        self.installations.iter().position(|i| same_path(&i.path, path))
+    }
+
+    // Adds (or relabels) an installation; the first one becomes active
+    pub(crate) fn add(&mut self, path: &Path, label: Option<String>) {
+        let label = label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        match self.find(path) {
+            Some(index) => {
+                if label.is_some() {
+                    self.installations[index].label = label;
+                }
+            }
+            None => self.installations.push(Installation { path: path.to_path_buf(), label, added_at: Utc::now() }),
+        }
+        if self.active.is_none() {
+            self.active = Some(path.to_path_buf());
+        }
+    }
+
+    // Forgets an installation without touching its files; another one takes over if it was active
+    pub(crate) fn remove(&mut self, path: &Path) -> Result<(), String> {
+        let index = self.find(path).ok_or_else(|| format!("Unknown installation '{}'", path.display()))?;
+        self.installations.remove(index);
+        if self.active.as_deref().map(|a| same_path(a, path)).unwrap_or(false) {
+            self.active = self.installations.iter().find(|i| is_installation(&i.path)).map(|i| i.path.clone());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_active(&mut self, path: &Path) -> Result<PathBuf, String> {
+        let index = self.find(path).ok_or_else(|| format!("Unknown installation '{}'", path.display()))?;
+        let target = self.installations[index].path.clone();
+        if !is_installation(&target) {
+            return Err(format!("Installation '{}' is not available (ps_env not found)", target.display()));
+        }
+        self.active = Some(target.clone());
+        Ok(target)
+    }
+
+    // The active installation if it is still on disk
+    pub(crate) fn active(&self) -> Option<PathBuf> {
+        self.active.clone().filter(|p| is_installation(p))
+    }
+
+    pub(crate) fn infos(&self) -> Vec<InstallationInfo> {
+        self.installations
+            .iter()
+            .map(|i| InstallationInfo {
+                path: i.path.to_string_lossy().to_string(),
+                label: i.label.clone(),
+                added_at: i.added_at,
+                active: self.active.as_deref().map(|a| same_path(a, &i.path)).unwrap_or(false),
+                available: is_installation(&i.path),
+            })
+            .collect()
+    }
+}
+
+fn registry_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle.path().app_config_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(REGISTRY_FILE))
+}
+
+// The same file for headless runs, which have no app handle to ask
+pub(crate) fn headless_registry_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER).join(REGISTRY_FILE))
+}
+
+pub(crate) fn load(app_handle: &tauri::AppHandle) -> InstallationRegistry {
+    load_from(registry_path(app_handle).ok().as_deref())
+}
+
+// Empty registry when the file is missing or unreadable; an install next to the app is picked up on first use
+pub(crate) fn load_from(path: Option<&Path>) -> InstallationRegistry {
+    let mut registry = path.map(read_registry).unwrap_or_default();
+    if let Some(dir) = exe_installation() {
+        if registry.find(&dir).is_none() {
+            registry.add(&dir, None);
+        }
+    }
+    registry
+}
+
+fn read_registry(path: &Path) -> InstallationRegistry {
+    match std::fs::read(path) {
+        Ok(raw) => match serde_json::from_slice::<InstallationRegistry>(&raw) {
+            Ok(registry) => registry,
+            Err(e) => {
+                log::warn!("Ignoring invalid installation registry {}: {}", path.display(), e);
+                InstallationRegistry::default()
+            }
+        },
+        Err(_) => InstallationRegistry::default(),
+    }
+}
+
+pub(crate) fn save(app_handle: &tauri::AppHandle, registry: &InstallationRegistry) -> Result<(), String> {
+    save_to(&registry_path(app_handle)?, registry)
+}
+
+pub(crate) fn save_to(path: &Path, registry: &InstallationRegistry) -> Result<(), String> {
+    let tmp = path.with_extension("json.tmp");
+    let raw = serde_json::to_vec_pretty(registry).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&tmp, raw)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to save installations to {}: {}", path.display(), e))
+}
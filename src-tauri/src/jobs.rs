@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::watch;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+// Finished jobs kept around so late status queries still get an answer
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub(crate) fn is_finished(self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct JobInfo {
+    pub(crate) id: String,
+    pub(crate) kind: String,
+    pub(crate) event_id: String,
+    pub(crate) state: JobState,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+// Event prefixes each kind of job emits with "-<event_id>" appended; the last one announces completion
+fn event_prefixes(kind: &str) -> &'static [&'static str] {
+    match kind {
+        "cli" => &["cli-output", "repo-install-progress", "cli-finished"],
+        "setup-env" => &["env-setup-progress", "env-setup-error", "env-setup-finished"],
+        "command" => &["command-output", "command-finished"],
+        _ => &[],
+    }
+}
+
+pub(crate) fn event_names(kind: &str, event_id: &str) -> Vec<String> {
+    event_prefixes(kind).iter().map(|prefix| format!("{}-{}", prefix, event_id)).collect()
+}
+
+pub(crate) fn finished_event(kind: &str, event_id: &str) -> Option<String> {
+    event_prefixes(kind).last().map(|prefix| format!("{}-{}", prefix, event_id))
+}
+
+// Handed to the job body; resolves once cancel_job is called for it
+#[derive(Clone)]
+pub(crate) struct CancelToken {
+    rx: watch::Receiver<bool>,
+}
+
+impl CancelToken {
+    pub(crate) async fn cancelled(&self) {
+        let mut rx = self.rx.clone();
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                // Registry dropped the job: it can no longer be cancelled
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+struct JobSlot {
+    info: JobInfo,
+    cancel_tx: watch::Sender<bool>,
+}
+
+pub(crate) struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<String, JobSlot>>,
+}
+
+impl JobRegistry {
+    pub(crate) fn new() -> Self {
+        Self { next_id: AtomicU64::new(1), jobs: Mutex::new(HashMap::new()) }
+    }
+
+    pub(crate) fn create(&self, kind: &str, event_id: &str) -> (String, CancelToken) {
+        let id = format!("job-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (cancel_tx, rx) = watch::channel(false);
+        let info = JobInfo {
+            id: id.clone(),
+            kind: kind.to_string(),
+            event_id: event_id.to_string(),
+            state: JobState::Queued,
+            created_at: Utc::now(),
+            finished_at: None,
+            error: None,
+        };
+        if let Ok(mut jobs) = self.jobs.lock() {
+            prune_finished(&mut jobs);
+            jobs.insert(id.clone(), JobSlot { info, cancel_tx });
+        }
+        (id, CancelToken { rx })
+    }
+
+    pub(crate) fn set_running(&self, id: &str) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(slot) = jobs.get_mut(id) {
+                if slot.info.state == JobState::Queued {
+                    slot.info.state = JobState::Running;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(&self, id: &str, state: JobState, error: Option<String>) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(slot) = jobs.get_mut(id) {
+                slot.info.state = state;
+                slot.info.finished_at = Some(Utc::now());
+                slot.info.error = error;
+            }
+        }
+    }
+
+    // Signals the job body; the body itself reports the final Cancelled state
+    pub(crate) fn cancel(&self, id: &str) -> Result<JobInfo, String> {
+        let jobs = self.jobs.lock().map_err(|_| "Job registry poisoned")?;
+        let slot = jobs.get(id).ok_or_else(|| format!("Unknown job '{}'", id))?;
+        if slot.info.state.is_finished() {
+            return Err(format!("Job '{}' has already finished", id));
+        }
+        let _ = slot.cancel_tx.send(true);
+        Ok(slot.info.clone())
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<JobInfo> {
+        self.jobs.lock().ok()?.get(id).map(|slot| slot.info.clone())
+    }
+
+    pub(crate) fn list(&self) -> Vec<JobInfo> {
+        let mut list: Vec<JobInfo> = match self.jobs.lock() {
+            Ok(jobs) => jobs.values().map(|slot| slot.info.clone()).collect(),
+            Err(_) => Vec::new(),
+        };
+        list.sort_by_key(|job| job.created_at);
+        list
+    }
+}
+
+fn prune_finished(jobs: &mut HashMap<String, JobSlot>) {
+    let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+        .values()
+        .filter(|slot| slot.info.state.is_finished())
+        .map(|slot| (slot.info.finished_at.unwrap_or(slot.info.created_at), slot.info.id.clone()))
+        .collect();
+    if finished.len() < MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
+
+// Time a process tree gets to exit after the polite signal before it is killed outright
+pub(crate) const TERMINATE_GRACE: Duration = Duration::from_secs(5);
+const GROUP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Asks a spawned shell and everything it started to exit, and kills whatever is left after
+// TERMINATE_GRACE. `leader_exited` resolves once the spawned process itself has been reaped.
+pub(crate) async fn terminate_process_tree(pid: u32, leader_exited: impl Future<Output = ()>) {
+    #[cfg(target_os = "windows")]
+    {
+        // taskkill /F does not ask first, so there is nothing to escalate
+        force_kill_tree(pid);
+        let _ = tokio::time::timeout(TERMINATE_GRACE, leader_exited).await;
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let deadline = tokio::time::Instant::now() + TERMINATE_GRACE;
+        signal_group(pid, libc::SIGTERM);
+        let _ = tokio::time::timeout_at(deadline, leader_exited).await;
+        // Children may outlive the shell that started them
+        while group_alive(pid) && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(GROUP_POLL_INTERVAL).await;
+        }
+        if group_alive(pid) {
+            log::warn!("Process group {} ignored SIGTERM, killing it", pid);
+            signal_group(pid, libc::SIGKILL);
+        }
+    }
+}
+
+// The same for app shutdown, where nothing async can run any more; waits at most `grace`
+pub(crate) fn terminate_process_trees_blocking(pids: &[u32], grace: Duration) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = grace;
+        for &pid in pids {
+            force_kill_tree(pid);
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        for &pid in pids {
+            signal_group(pid, libc::SIGTERM);
+        }
+        let deadline = std::time::Instant::now() + grace;
+        while pids.iter().any(|&pid| group_alive(pid)) && std::time::Instant::now() < deadline {
+            std::thread::sleep(GROUP_POLL_INTERVAL);
+        }
+        for &pid in pids.iter().filter(|&&pid| group_alive(pid)) {
+            signal_group(pid, libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn force_kill_tree(pid: u32) {
+    let mut cmd = std::process::Command::new("taskkill");
+    cmd.args(["/PID", &pid.to_string(), "/T", "/F"]);
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    let _ = cmd.status();
+}
+
+// Children are spawned as process group leaders, so the negative pid addresses the whole group
+#[cfg(not(target_os = "windows"))]
+fn signal_group(pid: u32, signal: libc::c_int) {
+    let Ok(pgid) = libc::pid_t::try_from(pid) else { return };
+    // SAFETY: kill has no memory-safety preconditions; a vanished group just returns ESRCH
+    unsafe { libc::kill(-pgid, signal) };
+}
+
+// Signal 0 only checks for existence; a zombie leader that was not reaped yet still counts
+#[cfg(not(target_os = "windows"))]
+fn group_alive(pid: u32) -> bool {
+    let Ok(pgid) = libc::pid_t::try_from(pid) else { return false };
+    // SAFETY: as in signal_group
+    unsafe { libc::kill(-pgid, 0) == 0 }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::{CommandExt as _, ExitStatusExt as _};
+
+    fn spawn_group(script: &str) -> tokio::process::Child {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.args(["-c", script]).process_group(0);
+        tokio::process::Command::from(cmd).spawn().unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_group_that_exits_on_term_is_not_killed() {
+        let mut child = spawn_group("sleep 30");
+        let pid = child.id().unwrap();
+        let started = tokio::time::Instant::now();
+        terminate_process_tree(pid, async { let _ = child.wait().await; }).await;
+        assert!(started.elapsed() < TERMINATE_GRACE);
+        assert!(!group_alive(pid));
+    }
+
+    #[tokio::test]
+    async fn a_group_that_ignores_term_is_killed_after_the_grace_period() {
+        // The trap keeps the shell alive through SIGTERM; its sleep child ignores it too
+        let mut child = spawn_group("trap '' TERM; sleep 30 & wait; wait");
+        let pid = child.id().unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let started = tokio::time::Instant::now();
+        let status = tokio::spawn(async move { child.wait().await });
+        terminate_process_tree(pid, std::future::pending::<()>()).await;
+        assert!(started.elapsed() >= TERMINATE_GRACE);
+        let status = tokio::time::timeout(Duration::from_secs(5), status).await.unwrap().unwrap().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::supervisor::LaunchSpec;
+
+// Entry points tried, in order, when a Unix launcher has to be generated
+#[cfg(not(target_os = "windows"))]
+const ENTRY_CANDIDATES: &[&str] = &["launch.py", "main.py", "webui.py", "app.py", "run.py", "server.py"];
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct LaunchResult {
+    pub(crate) success: bool,
+    pub(crate) repo: String,
+    pub(crate) pid: Option<u32>,
+    pub(crate) launcher: String,
+    pub(crate) generated: bool,
+    pub(crate) message: String,
+}
+
+pub(crate) struct ResolvedLauncher {
+    pub(crate) script: PathBuf,
+    pub(crate) generated: bool,
+    pub(crate) spec: LaunchSpec,
+}
+
+pub(crate) fn launcher_file_name(repo: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("start_{}.bat", repo)
+    } else {
+        format!("start_{}.sh", repo)
+    }
+}
+
+// Whether launch_repository can start the repo: an existing script, or on Unix an entry point to generate one for
+pub(crate) fn has_launcher(install_dir: &Path, repo: &str) -> bool {
+    let repo_dir = install_dir.join("repos").join(repo);
+    if repo_dir.join(launcher_file_name(repo)).is_file() {
+        return true;
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        find_entry_point(&repo_dir).is_some()
+    }
+    #[cfg(target_os = "windows")]
+    {
+        false
+    }
+}
+
+// Finds the repo's start script (generating one on Unix if needed) and describes how to run it
+pub(crate) fn resolve_launcher(install_dir: &Path, repo: &str) -> Result<ResolvedLauncher, String> {
+    let repo_dir = install_dir.join("repos").join(repo);
+    if !repo_dir.is_dir() {
+        return Err(format!("Repository '{}' is not installed", repo));
+    }
+    let script = repo_dir.join(launcher_file_name(repo));
+
+    #[cfg(target_os = "windows")]
+    {
+        if !script.is_file() {
+            return Err(format!("Launcher {} not found", script.display()));
+        }
+        let spec = LaunchSpec {
+            program: "cmd".to_string(),
+            args: vec!["/C".to_string(), script.to_string_lossy().to_string()],
+            working_dir: repo_dir,
+            env: Vec::new(),
+        };
+        Ok(ResolvedLauncher { script, generated: false, spec })
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let generated = if script.is_file() {
+            false
+        } else {
+            generate_unix_launcher(install_dir, repo, &repo_dir, &script)?;
+            true
+        };
+        let spec = LaunchSpec {
+            program: "bash".to_string(),
+            args: vec![script.to_string_lossy().to_string()],
+            working_dir: repo_dir,
+            env: unix_repo_env(install_dir, repo),
+        };
+        Ok(ResolvedLauncher { script, generated, spec })
+    }
+}
+
+// Venv of the repo plus the portable tools, ahead of the inherited PATH
+#[cfg(not(target_os = "windows"))]
+fn unix_repo_env(install_dir: &Path, repo: &str) -> Vec<(String, String)> {
+    let env_dir = install_dir.join("envs").join(repo);
+    let ps_env = install_dir.join("ps_env");
+    let mut path_dirs: Vec<PathBuf> = vec![
+        env_dir.join("bin"),
+        ps_env.join("python").join("bin"),
+        ps_env.join("git").join("bin"),
+        ps_env.join("ffmpeg"),
+        ps_env.join("ffmpeg").join("bin"),
+    ];
+    path_dirs.retain(|p| p.is_dir());
+    if let Some(existing) = std::env::var_os("PATH") {
+        path_dirs.extend(std::env::split_paths(&existing));
+    }
+
+    let mut env = vec![("PYTHONUNBUFFERED".to_string(), "1".to_string())];
+    if let Ok(path) = std::env::join_paths(path_dirs) {
+        env.push(("PATH".to_string(), path.to_string_lossy().to_string()));
+    }
+    if env_dir.is_dir() {
+        env.push(("VIRTUAL_ENV".to_string(), env_dir.to_string_lossy().to_string()));
+    }
+    env
+}
+
+#[cfg(not(target_os = "windows"))]
+fn generate_unix_launcher(install_dir: &Path, repo: &str, repo_dir: &Path, script: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let entry = find_entry_point(repo_dir)
+        .ok_or_else(|| format!("No launcher for '{}' and no known entry point ({})", repo, ENTRY_CANDIDATES.join(", ")))?;
+
+    let env_python = install_dir.join("envs").join(repo).join("bin").join("python");
+    let portable_python = install_dir.join("ps_env").join("python").join("bin").join("python3");
+    let python = if env_python.is_file() {
+        shell_quote(&env_python.to_string_lossy())
+    } else if portable_python.is_file() {
+        shell_quote(&portable_python.to_string_lossy())
+    } else {
+        "python3".to_string()
+    };
+
+    let content = format!(
+        "#!/usr/bin/env bash\n# Generated by PortableSource\nset -e\ncd {}\nexec {} {} \"$@\"\n",
+        shell_quote(&repo_dir.to_string_lossy()),
+        python,
+        shell_quote(entry),
+    );
+    std::fs::write(script, content).map_err(|e| format!("Failed to write {}: {}", script.display(), e))?;
+    std::fs::set_permissions(script, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Failed to make {} executable: {}", script.display(), e))?;
+    log::info!("Generated launcher {}", script.display());
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn find_entry_point(repo_dir: &Path) -> Option<&'static str> {
+    ENTRY_CANDIDATES.iter().copied().find(|name| repo_dir.join(name).is_file())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
@@ -2,8 +2,7 @@ use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::fs;
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 use std::sync::Mutex;
 use std::sync::Arc;
 use std::collections::VecDeque;
@@ -15,11 +14,65 @@ use portablesource_rs::envs_manager::PortableEnvironmentManager as PsEnvManager;
 use portablesource_rs::repository_installer::RepositoryInstaller as PsRepoInstaller;
 use portablesource_rs::utils as ps_utils;
 
+mod catalog;
+mod cli_action;
+mod config_service;
+mod console_settings;
+mod control_api;
+mod disk_space;
+mod env_doctor;
+mod headless;
+mod install_path;
+mod install_watcher;
+mod installations;
+mod installed_repos;
+mod jobs;
+mod launcher;
+mod log_bridge;
+mod log_files;
+mod paths;
+mod process_stream;
+mod proxy;
+mod repo_progress;
+mod repo_removal;
+mod supervisor;
+#[cfg(test)]
+mod test_support;
+mod updates;
+
+use catalog::{CatalogCache, CatalogClient, CatalogPage};
+use cli_action::{BufferedSink, CliAction, OutputSink, OutputStream};
+use config_service::{ConfigService, OperationGuard};
+use console_settings::{ConsoleSettings, LogLevel};
+use control_api::{ControlApi, ControlApiSettings, ControlApiStatus};
+use disk_space::{DiskAction, SpacePreflight};
+use env_doctor::{EnvironmentDiagnosis, Tool};
+use install_path::InstallPathReport;
+use install_watcher::InstallWatcher;
+use installations::{InstallationInfo, InstallationRegistry};
+use installed_repos::InstalledRepository;
+use jobs::{CancelToken, JobInfo, JobRegistry, JobState};
+use launcher::LaunchResult;
+use log_files::LogFileSink;
+use paths::{InstallRoot, RepoName};
+use proxy::ProxyError;
+use repo_progress::ProgressGuard;
+use repo_removal::RemovalReport;
+use supervisor::{LaunchSpec, RepoOutputLine, RunningRepo, Supervisor};
+use updates::StagedUpdate;
+
 // Keep shared config to reduce redundant disk I/O
 struct AppState { 
-    config: Mutex<PsConfigManager>,
+    config: ConfigService,
     log_buffer: Arc<Mutex<VecDeque<LogEntry>>>,
-    console_enabled: Arc<Mutex<bool>>,
+    console_settings: Mutex<ConsoleSettings>,
+    log_files: Mutex<LogFileSink>,
+    jobs: JobRegistry,
+    supervisor: Supervisor,
+    catalog: CatalogClient,
+    installations: Mutex<InstallationRegistry>,
+    install_watcher: InstallWatcher,
+    control_api: ControlApi,
 }
 
 #[cfg(target_os = "windows")]
@@ -29,34 +82,61 @@ use std::os::windows::process::CommandExt;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct LogEntry {
     timestamp: DateTime<Utc>,
-    level: String,
+    level: LogLevel,
     source: String, // "GUI" or "CLI"
     message: String,
     module: Option<String>,
 }
 
-// Console settings
-#[derive(Debug, Serialize, Deserialize)]
-struct ConsoleSettings {
-    enabled: bool,
-    max_entries: usize,
-    log_level: String,
-}
-
 #[tauri::command]
-async fn proxy_request(url: String) -> Result<String, String> {
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| e.to_string())?;
+async fn proxy_request(state: tauri::State<'_, AppState>, url: String) -> Result<String, ProxyError> {
+    let mut allowed = proxy::default_allowed_hosts();
+    allowed.extend(state.catalog.host());
+    proxy::fetch_text(&url, &allowed).await.map_err(|e| {
+        log::warn!("proxy_request({}) rejected: {}", url, e);
+        e
+    })
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Request failed with status: {}", response.status()));
-    }
+// Catalog responses are cached under the install dir when one is known
+fn catalog_cache(state: &AppState, install_path: Option<String>) -> Option<CatalogCache> {
+    let install_dir = match install_path {
+        Some(path) if !path.is_empty() => match InstallRoot::new(&path) {
+            Ok(root) => root.path().to_path_buf(),
+            Err(e) => {
+                log::warn!("Not caching catalog responses: {}", e);
+                return None;
+            }
+        },
+        _ => state.config.install_dir()?,
+    };
+    Some(CatalogCache::for_install(&install_dir))
+}
 
-    let body = response.text().await.map_err(|e| e.to_string())?;
-    Ok(body)
+#[tauri::command]
+async fn catalog_top(
+    state: tauri::State<'_, AppState>,
+    install_path: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<CatalogPage, ProxyError> {
+    let catalog = state.catalog.clone();
+    let cache = catalog_cache(&state, install_path);
+    catalog.top(cache.as_ref(), limit, offset).await
 }
 
+#[tauri::command]
+async fn catalog_search(
+    state: tauri::State<'_, AppState>,
+    install_path: Option<String>,
+    query: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<CatalogPage, ProxyError> {
+    let catalog = state.catalog.clone();
+    let cache = catalog_cache(&state, install_path);
+    catalog.search(cache.as_ref(), &query, limit, offset).await
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct InstallResult {
@@ -84,6 +164,7 @@ struct StreamOutput {
 struct StreamFinished {
     success: bool,
     exit_code: Option<i32>,
+    status: JobState,
 }
 
 #[derive(Clone, serde::Serialize)]
@@ -93,24 +174,40 @@ struct UiProgressEvent {
     total: usize,
 }
 
+// README rules plus writability, path length, free space and drive type
 #[tauri::command]
-async fn set_install_path(path: String) -> Result<InstallResult, String> {
-    // Normalize to include leaf 'portablesource' folder to ensure stable structure
-    let mut target = PathBuf::from(&path);
-    if !target
-        .file_name()
-        .and_then(|s| s.to_str())
-        .map(|n| n.eq_ignore_ascii_case("portablesource"))
-        .unwrap_or(false)
-    {
-        target = target.join("portablesource");
+async fn validate_install_path(app_handle: tauri::AppHandle, path: String) -> Result<InstallPathReport, String> {
+    checked_install_path(&app_handle, &path).await
+}
+
+async fn checked_install_path(app_handle: &tauri::AppHandle, path: &str) -> Result<InstallPathReport, String> {
+    let downloads = app_handle.path().download_dir().ok();
+    let path = path.to_string();
+    tauri::async_runtime::spawn_blocking(move || install_path::validate(&path, downloads.as_deref()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Paths that will be created or written to get the full README checks, not just InstallRoot's
+async fn validated_install_dir(app_handle: &tauri::AppHandle, path: &str) -> Result<PathBuf, String> {
+    let report = checked_install_path(app_handle, path).await?;
+    if !report.valid {
+        return Err(report.error_summary());
     }
+    // Normalize to include leaf 'portablesource' folder to ensure stable structure
+    Ok(install_path::normalize(path))
+}
+
+#[tauri::command]
+async fn set_install_path(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>, path: String) -> Result<InstallResult, String> {
+    let target = validated_install_dir(&app_handle, &path).await?;
 
     fs::create_dir_all(&target).map_err(|e| format!("Failed to create directory: {}", e))?;
 
     // Create ps_env directory to mark this as a valid installation
     let ps_env_dir = target.join("ps_env");
     fs::create_dir_all(&ps_env_dir).map_err(|e| format!("Failed to create ps_env directory: {}", e))?;
+    register_installation(&app_handle, &state, &target)?;
 
     Ok(InstallResult {
         success: true,
@@ -119,47 +216,123 @@ async fn set_install_path(path: String) -> Result<InstallResult, String> {
     })
 }
 
+// The active installation from the registry, else one next to the executable
+fn active_installation(state: &AppState) -> Option<PathBuf> {
+    let active = state.installations.lock().ok().and_then(|registry| registry.active());
+    active.or_else(installations::exe_installation)
+}
+
 #[tauri::command]
-async fn get_install_path() -> Result<String, String> {
-    // Try to find installation near current executable
-    if let Ok(current_exe) = std::env::current_exe() {
-        if let Some(exe_dir) = current_exe.parent() {
-            let ps_env = exe_dir.join("ps_env");
-            if ps_env.exists() {
-                return Ok(exe_dir.to_string_lossy().to_string());
-            }
-        }
-    }
-    
-    Err("Install path not found".to_string())
+async fn get_install_path(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    active_installation(&state)
+        .map(|dir| dir.to_string_lossy().to_string())
+        .ok_or_else(|| "Install path not found".to_string())
 }
 
 #[tauri::command]
-async fn find_cli_installation() -> Result<String, String> {
-    // 1) Попытка найти установку рядом с текущим исполняемым файлом
-    if let Ok(current_exe) = std::env::current_exe() {
-        if let Some(exe_dir) = current_exe.parent() {
-            let ps_env = exe_dir.join("ps_env");
-            if ps_env.exists() {
-                return Ok(exe_dir.to_string_lossy().to_string());
-            }
+async fn find_cli_installation(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    active_installation(&state)
+        .map(|dir| dir.to_string_lossy().to_string())
+        .ok_or_else(|| "Installation path not found".to_string())
+}
+
+// Records a freshly set up installation and makes it the active one
+fn register_installation(app_handle: &tauri::AppHandle, state: &AppState, install_dir: &Path) -> Result<(), String> {
+    let mut registry = state.installations.lock().map_err(|_| "State poisoned")?;
+    registry.add(install_dir, None);
+    registry.set_active(install_dir)?;
+    installations::save(app_handle, &registry)?;
+    state.config.switch_to(Some(install_dir))?;
+    follow_installation(app_handle, state, Some(install_dir));
+    Ok(())
+}
+
+// Background services bound to the active installation. Watching is best effort; without it the
+// UI only misses changes made outside the app. The control API records its own start errors.
+fn follow_installation(app_handle: &tauri::AppHandle, state: &AppState, install_dir: Option<&Path>) {
+    if let Err(e) = state.install_watcher.follow(app_handle, install_dir) {
+        log::warn!("{}", e);
+    }
+    state.control_api.follow(app_handle, install_dir);
+}
+
+// Points AppState (config and console settings) at `install_dir` and tells the UI
+fn activate_installation(app_handle: &tauri::AppHandle, state: &AppState, install_dir: Option<&Path>) -> Result<(), String> {
+    state.config.switch_to(install_dir)?;
+    follow_installation(app_handle, state, install_dir);
+    if let Some(dir) = install_dir {
+        let settings = console_settings::load(dir);
+        if let Ok(mut log_files) = state.log_files.lock() {
+            log_files.set_install_dir(dir);
         }
+        *state.console_settings.lock().map_err(|_| "State poisoned")? = settings;
     }
+    let path = install_dir.map(|dir| dir.to_string_lossy().to_string());
+    let _ = app_handle.emit("installation-changed", path);
+    Ok(())
+}
 
-    Err("Installation path not found".to_string())
+#[tauri::command]
+async fn list_installations(state: tauri::State<'_, AppState>) -> Result<Vec<InstallationInfo>, String> {
+    Ok(state.installations.lock().map_err(|_| "State poisoned")?.infos())
 }
 
+// Registers an existing installation (a folder containing ps_env) without switching to it
 #[tauri::command]
-async fn download_and_install_cli(state: tauri::State<'_, AppState>, install_path: String) -> Result<InstallResult, String> {
-    let mut install_dir = PathBuf::from(&install_path);
-    if !install_dir
-        .file_name()
-        .and_then(|s| s.to_str())
-        .map(|n| n.eq_ignore_ascii_case("portablesource"))
-        .unwrap_or(false)
-    {
-        install_dir = install_dir.join("portablesource");
+async fn add_installation(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    label: Option<String>,
+) -> Result<Vec<InstallationInfo>, String> {
+    let root = InstallRoot::new(&path)?;
+    if !installations::is_installation(root.path()) {
+        return Err(format!("No PortableSource installation at '{}' (ps_env not found)", path));
+    }
+    let mut registry = state.installations.lock().map_err(|_| "State poisoned")?;
+    registry.add(root.path(), label);
+    installations::save(&app_handle, &registry)?;
+    Ok(registry.infos())
+}
+
+// Forgets an installation; its files stay on disk
+#[tauri::command]
+async fn remove_installation(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>, path: String) -> Result<Vec<InstallationInfo>, String> {
+    let (previous, active, infos) = {
+        let mut registry = state.installations.lock().map_err(|_| "State poisoned")?;
+        let previous = registry.active.clone();
+        registry.remove(Path::new(&path))?;
+        installations::save(&app_handle, &registry)?;
+        (previous, registry.active.clone(), registry.infos())
+    };
+    if previous != active {
+        activate_installation(&app_handle, &state, active.as_deref())?;
     }
+    Ok(infos)
+}
+
+#[tauri::command]
+async fn switch_installation(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>, path: String) -> Result<Vec<InstallationInfo>, String> {
+    log::info!("switch_installation(path={})", path);
+    let (target, infos) = {
+        let mut registry = state.installations.lock().map_err(|_| "State poisoned")?;
+        let target = registry.set_active(Path::new(&path))?;
+        installations::save(&app_handle, &registry)?;
+        (target, registry.infos())
+    };
+    activate_installation(&app_handle, &state, Some(&target))?;
+    Ok(infos)
+}
+
+#[tauri::command]
+async fn download_and_install_cli(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    install_path: String,
+    ignore_disk_space: Option<bool>,
+) -> Result<InstallResult, String> {
+    let install_dir = validated_install_dir(&app_handle, &install_path).await?;
+    ensure_disk_space(&app_handle, &install_dir, DiskAction::SetupEnv, ignore_disk_space.unwrap_or(false)).await?;
     fs::create_dir_all(&install_dir)
         .map_err(|e| format!("Failed to create install directory: {}", e))?;
     
@@ -169,209 +342,253 @@ async fn download_and_install_cli(state: tauri::State<'_, AppState>, install_pat
     ps_utils::create_directory_structure(&install_dir)
         .map_err(|e| e.to_string())?;
 
-    let mut cfg = state.config.lock().map_err(|_| "State poisoned")?.clone();
-    if cfg.get_config().install_path.as_os_str().is_empty() {
-        // Устанавливаем один раз без повторных сохранений в циклах
-        cfg.set_install_path(install_dir.clone()).map_err(|e| e.to_string())?;
-    }
-
-    let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg.clone());
+    let (cfg, _operation) = state.config.begin_operation(&install_dir).await?;
+    let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg);
     env_mgr.setup_environment().await.map_err(|e| e.to_string())?;
+    // Activating the installation loads the config environment setup just wrote
+    register_installation(&app_handle, &state, &install_dir)?;
+    Ok(InstallResult { success: true, message: "Environment installed successfully".to_string(), normalized_path: Some(install_dir.to_string_lossy().to_string()) })
+}
+
+#[tauri::command]
+async fn run_cli_command(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    install_path: String,
+    args: Vec<String>,
+    event_id: Option<String>,
+    ignore_disk_space: Option<bool>,
+) -> Result<CommandResult, String> {
+    log::debug!("run_cli_command called with args: {:?}", args);
 
-    // Reload config from disk to reflect changes performed by environment setup
-    if let Ok(updated) = PsConfigManager::new(None) {
-        *state.config.lock().map_err(|_| "State poisoned")? = updated;
+    let action = match CliAction::parse(&args) {
+        Ok(action) => action,
+        Err(e) => {
+            return Ok(CommandResult { success: false, stdout: String::new(), stderr: e.to_string(), exit_code: Some(2) });
+        }
+    };
+
+    let install_dir = InstallRoot::new(&install_path)?.path().to_path_buf();
+    if let Some(disk_action) = DiskAction::for_cli(&action) {
+        if let Err(e) = ensure_disk_space(&app_handle, &install_dir, disk_action, ignore_disk_space.unwrap_or(false)).await {
+            return Ok(CommandResult { success: false, stdout: String::new(), stderr: e, exit_code: Some(1) });
+        }
+    }
+    let (cfg, _operation) = if action.writes_config() {
+        let (cfg, guard) = state.config.begin_operation(&install_dir).await?;
+        (cfg, Some(guard))
     } else {
-        *state.config.lock().map_err(|_| "State poisoned")? = cfg;
+        (state.config.for_operation(&install_dir)?, None)
+    };
+
+    let mut sink = BufferedSink::default();
+    let progress = event_id.and_then(|id| track_repo_progress(&app_handle, &action, &install_dir, &id));
+    let success = cli_action::dispatch(&action, &install_dir, cfg, &mut sink).await;
+    drop(progress);
+
+    // Refresh config from disk to pick persisted changes if any
+    if let Err(e) = state.config.reload(&install_dir) {
+        log::warn!("Failed to reload config: {}", e);
     }
-    Ok(InstallResult { success: true, message: "Environment installed successfully".to_string(), normalized_path: Some(install_dir.to_string_lossy().to_string()) })
+
+    Ok(CommandResult { success, stdout: sink.stdout, stderr: sink.stderr, exit_code: Some(if success { 0 } else { 1 }) })
+}
+
+// Refuses to start a download that cannot fit on the install volume, unless the user overrides it
+async fn ensure_disk_space(app_handle: &tauri::AppHandle, install_dir: &Path, action: DiskAction, ignore: bool) -> Result<(), String> {
+    let preflight = disk_space_preflight(install_dir, action).await?;
+    accept_preflight(app_handle, &preflight, ignore)
+}
+
+fn accept_preflight(app_handle: &tauri::AppHandle, preflight: &SpacePreflight, ignore: bool) -> Result<(), String> {
+    if preflight.sufficient {
+        return Ok(());
+    }
+    if !ignore {
+        return Err(preflight.shortfall_message());
+    }
+    let _ = push_log_entry(
+        app_handle,
+        LogLevel::Warn,
+        "GUI",
+        format!("Disk space check overridden: {}", preflight.shortfall_message()),
+        Some("disk".to_string()),
+    );
+    Ok(())
+}
+
+async fn disk_space_preflight(install_dir: &Path, action: DiskAction) -> Result<SpacePreflight, String> {
+    let install_dir = install_dir.to_path_buf();
+    tauri::async_runtime::spawn_blocking(move || {
+        let components = disk_space::components_for(action, &install_dir);
+        disk_space::preflight(&install_dir, &components)
+    })
+    .await
+    .map_err(|e| e.to_string())
 }
 
+// Per-component size estimate for an action compared with the free space at install_path
 #[tauri::command]
-async fn run_cli_command(state: tauri::State<'_, AppState>, install_path: String, args: Vec<String>) -> Result<CommandResult, String> {
-    println!("[DEBUG] run_cli_command called with args: {:?}", args);
-    
-    let install_dir = PathBuf::from(&install_path);
-    let mut cfg = state.config.lock().map_err(|_| "State poisoned")?.clone();
-    if cfg.get_config().install_path.as_os_str().is_empty() {
-        cfg.set_install_path(install_dir.clone()).map_err(|e| e.to_string())?;
+async fn check_disk_space(install_path: String, action: DiskAction) -> Result<SpacePreflight, String> {
+    disk_space_preflight(InstallRoot::new(&install_path)?.path(), action).await
+}
+
+// Reports repo-install-progress-<id> while an install or update runs; None for other actions
+fn track_repo_progress(app_handle: &tauri::AppHandle, action: &CliAction, install_dir: &Path, event_id: &str) -> Option<ProgressGuard> {
+    let event = format!("repo-install-progress-{}", event_id);
+    match action {
+        CliAction::InstallRepo(repo) => Some(repo_progress::track(app_handle, event, install_dir, repo, false)),
+        CliAction::UpdateRepo(repo) => Some(repo_progress::track(app_handle, event, install_dir, repo.as_str(), true)),
+        _ => None,
     }
-    let mut stdout = String::new();
-    let mut stderr = String::new();
-    let mut success = true;
-
-    if args.contains(&"--setup-env".to_string()) {
-        let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg.clone());
-        match env_mgr.setup_environment().await {
-            Ok(_) => { stdout.push_str("Environment setup completed successfully\n"); }
-            Err(e) => { success = false; stderr.push_str(&e.to_string()); }
-        }
-    } else if let Some(pos) = args.iter().position(|a| a == "--install-repo") {
-        if let Some(repo) = args.get(pos + 1) {
-            let mut installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-            match installer.install_repository(repo).await {
-                Ok(_) => {
-                    println!("[DEBUG] Repository installation successful");
-                    stdout.push_str("Repository installed successfully\n");
-                }
-                Err(e) => { 
-                    println!("[DEBUG] Repository installation failed: {}", e);
-                    success = false; 
-                    stderr.push_str(&e.to_string()); 
-                }
+}
+
+// Records the final job state and emits the matching *-finished-<id> event
+fn finish_job(
+    app_handle: &tauri::AppHandle,
+    job_id: &str,
+    event: &str,
+    status: JobState,
+    exit_code: Option<i32>,
+    error: Option<String>,
+) {
+    app_handle.state::<AppState>().jobs.finish(job_id, status, error);
+    let _ = app_handle.emit(
+        event,
+        StreamFinished { success: status == JobState::Succeeded, exit_code, status },
+    );
+}
+
+// Keeps a job queued, and cancellable, while another config-writing operation runs on the same
+// installation. None when the job ended while waiting; it has already been finished then.
+async fn start_job_operation(
+    app_handle: &tauri::AppHandle,
+    job_id: &str,
+    event: &str,
+    install_dir: &Path,
+    cancel: &CancelToken,
+    writes_config: bool,
+) -> Option<(PsConfigManager, Option<OperationGuard>)> {
+    let state = app_handle.state::<AppState>();
+    let operation = if writes_config {
+        tokio::select! {
+            result = state.config.begin_operation(install_dir) => result.map(|(cfg, guard)| (cfg, Some(guard))),
+            _ = cancel.cancelled() => {
+                finish_job(app_handle, job_id, event, JobState::Cancelled, None, None);
+                return None;
             }
-        } else { 
-            println!("[DEBUG] Missing repository name for --install-repo");
-            success = false; 
-            stderr.push_str("Missing repository name"); 
         }
-    } else if let Some(pos) = args.iter().position(|a| a == "--update-repo") {
-        if let Some(repo) = args.get(pos + 1) {
-            let mut installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-            match installer.update_repository(repo).await {
-                Ok(_) => stdout.push_str("Repository updated successfully\n"),
-                Err(e) => { success = false; stderr.push_str(&e.to_string()); }
-            }
-        } else { success = false; stderr.push_str("Missing repository name"); }
-    } else if let Some(pos) = args.iter().position(|a| a == "--delete-repo") {
-        if let Some(repo) = args.get(pos + 1) {
-            let installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-            match installer.delete_repository(repo) {
-                Ok(_) => stdout.push_str("Repository deleted successfully\n"),
-                Err(e) => { success = false; stderr.push_str(&e.to_string()); }
-            }
-        } else { success = false; stderr.push_str("Missing repository name"); }
-    } else if args.contains(&"--version".to_string()) {
-        stdout.push_str(&format!("PortableSource version: {}\n", portablesource_rs::config::VERSION));
-    } else if args.contains(&"--check-env".to_string()) {
-        let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg.clone());
-        match env_mgr.check_environment_status() {
-            Ok(status) => {
-                stdout.push_str(&format!("Environment exists: {}\n", if status { "YES" } else { "NO" }));
-                stdout.push_str(&format!("Setup completed: {}\n", if cfg.is_environment_setup_completed() { "YES" } else { "NO" }));
-            }
-            Err(e) => { success = false; stderr.push_str(&e.to_string()); }
+    } else {
+        state.config.for_operation(install_dir).map(|cfg| (cfg, None))
+    };
+    match operation {
+        Ok(operation) => {
+            state.jobs.set_running(job_id);
+            Some(operation)
         }
-    } else if args.contains(&"list-repos".to_string()) {
-        let installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-        match installer.list_repositories() {
-            Ok(repos) => {
-                if repos.is_empty() {
-                    stdout.push_str("No repositories installed\n");
-                } else {
-                    stdout.push_str("Installed repositories:\n");
-                    for repo in repos {
-                        stdout.push_str(&format!("  - {}\n", repo));
-                    }
-                }
-            }
-            Err(e) => { success = false; stderr.push_str(&e.to_string()); }
+        Err(e) => {
+            finish_job(app_handle, job_id, event, JobState::Failed, Some(1), Some(e));
+            None
         }
-    } else {
-        println!("[DEBUG] Unsupported command arguments: {:?}", args);
-        success = false;
-        stderr.push_str("Unsupported command arguments");
-    }
-
-    // Refresh config from disk to pick persisted changes if any
-    if let Ok(updated) = PsConfigManager::new(None) {
-        *state.config.lock().map_err(|_| "State poisoned")? = updated;
-    } else {
-        *state.config.lock().map_err(|_| "State poisoned")? = cfg;
     }
-    
-    Ok(CommandResult { success, stdout, stderr, exit_code: Some(if success { 0 } else { 1 }) })
 }
 
 #[tauri::command]
 async fn run_command_stream(
     app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
     command: String,
     working_dir: Option<String>,
     event_id: String,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let mut cmd = if cfg!(target_os = "windows") {
-        let mut cmd = Command::new("powershell");
+        let mut cmd = tokio::process::Command::new("powershell");
         cmd.args(["-Command", &command]);
         cmd
     } else {
-        let mut cmd = Command::new("sh");
+        let mut cmd = tokio::process::Command::new("sh");
         cmd.args(["-c", &command]);
         cmd
     };
-    
+
     if let Some(dir) = working_dir {
         cmd.current_dir(dir);
     }
-    
-    cmd.stdout(Stdio::piped())
+
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
         .stderr(Stdio::piped());
-    
+
     // Hide console window on Windows
     #[cfg(target_os = "windows")]
     cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
-    
+
+    // Own process group so cancel_job can take down everything the shell started
+    #[cfg(not(target_os = "windows"))]
+    cmd.process_group(0);
+
     let mut child = cmd.spawn()
         .map_err(|e| format!("Failed to spawn command: {}", e))?;
-    
-    let stdout = child.stdout.take().unwrap();
-    let stderr = child.stderr.take().unwrap();
-    
-    let stdout_reader = BufReader::new(stdout);
-    let stderr_reader = BufReader::new(stderr);
-    
-    let app_handle_stdout = app_handle.clone();
-    let event_id_stdout = event_id.clone();
-    
-    // Handle stdout in a separate task
-    let stdout_task = tokio::spawn(async move {
-        for line in stdout_reader.lines() {
-            if let Ok(line) = line {
-                let _ = app_handle_stdout.emit(
-                    &format!("command-output-{}", event_id_stdout),
-                    StreamOutput {
-                        stream: "stdout".to_string(),
-                        data: line,
-                    },
-                );
-            }
-        }
-    });
-    
-    let app_handle_stderr = app_handle.clone();
-    let event_id_stderr = event_id.clone();
-    
-    // Handle stderr in a separate task
-    let stderr_task = tokio::spawn(async move {
-        for line in stderr_reader.lines() {
-            if let Ok(line) = line {
-                let _ = app_handle_stderr.emit(
-                    &format!("command-output-{}", event_id_stderr),
-                    StreamOutput {
-                        stream: "stderr".to_string(),
-                        data: line,
-                    },
-                );
+
+    let (job_id, cancel) = state.jobs.create("command", &event_id);
+    state.jobs.set_running(&job_id);
+
+    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+    let pump = process_stream::spawn_output_pump(
+        app_handle.clone(),
+        format!("command-output-{}", event_id),
+        stdout,
+        stderr,
+    );
+
+    let id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let (status, exit_code, error) = tokio::select! {
+            result = child.wait() => match result {
+                Ok(status) => {
+                    let job_status = if status.success() { JobState::Succeeded } else { JobState::Failed };
+                    (job_status, status.code(), None)
+                }
+                Err(e) => (JobState::Failed, None, Some(format!("Failed to wait for command: {}", e))),
+            },
+            _ = cancel.cancelled() => {
+                if let Some(pid) = child.id() {
+                    jobs::terminate_process_tree(pid, async {
+                        let _ = child.wait().await;
+                    })
+                    .await;
+                }
+                let _ = child.kill().await;
+                (JobState::Cancelled, None, None)
             }
+        };
+
+        // Deliver the last batch before announcing completion
+        if tokio::time::timeout(process_stream::DRAIN_TIMEOUT, pump).await.is_err() {
+            log::warn!("Output of job {} still open after the command exited; not waiting for it", id);
         }
+
+        finish_job(&app_handle, &id, &format!("command-finished-{}", event_id), status, exit_code, error);
     });
-    
-    // Wait for the process to finish
-    let status = child.wait().map_err(|e| format!("Failed to wait for command: {}", e))?;
-    
-    // Wait for both tasks to complete
-    let _ = tokio::join!(stdout_task, stderr_task);
-    
-    // Emit completion event
-    let _ = app_handle.emit(
-        &format!("command-finished-{}", event_id),
-        StreamFinished {
-            success: status.success(),
-            exit_code: status.code(),
-        },
-    );
-    
-    Ok(())
+
+    Ok(job_id)
+}
+
+// Streams dispatcher output as cli-output-<id> events and mirrors milestones into the console
+struct EventSink {
+    app_handle: tauri::AppHandle,
+    event: String,
+}
+
+impl OutputSink for EventSink {
+    fn line(&mut self, stream: OutputStream, data: &str) {
+        let _ = self.app_handle.emit(&self.event, StreamOutput { stream: stream.as_str().to_string(), data: data.to_string() });
+    }
+
+    fn status(&mut self, level: LogLevel, module: &str, message: &str) {
+        let _ = push_log_entry(&self.app_handle, level, "CLI", message.to_string(), Some(module.to_string()));
+    }
 }
 
 #[tauri::command]
@@ -381,135 +598,62 @@ async fn run_cli_command_stream(
     install_path: String,
     args: Vec<String>,
     event_id: String,
-) -> Result<(), String> {
-    // Эмуляция потокового вывода поверх библиотечных вызовов
-    let install_dir = PathBuf::from(&install_path);
-    let mut cfg = state.config.lock().map_err(|_| "State poisoned")?.clone();
-    if cfg.get_config().install_path.as_os_str().is_empty() {
-        let _ = cfg.set_install_path(install_dir.clone());
+    ignore_disk_space: Option<bool>,
+) -> Result<String, String> {
+    let install_dir = InstallRoot::new(&install_path)?.path().to_path_buf();
+    // Parse errors are reported on the job's output stream below
+    if let Some(disk_action) = CliAction::parse(&args).ok().as_ref().and_then(DiskAction::for_cli) {
+        ensure_disk_space(&app_handle, &install_dir, disk_action, ignore_disk_space.unwrap_or(false)).await?;
     }
 
-    let app = app_handle.clone();
-    let ev_id = event_id.clone();
-
-    let emit_line = move |stream: &str, data: String| {
-        let _ = app.emit(&format!("cli-output-{}", ev_id), StreamOutput { stream: stream.to_string(), data });
-    };
+    let (job_id, cancel) = state.jobs.create("cli", &event_id);
+    let id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let finished = format!("cli-finished-{}", event_id);
+        let parsed = CliAction::parse(&args);
+        let writes_config = parsed.as_ref().is_ok_and(CliAction::writes_config);
+        let Some((cfg, _operation)) = start_job_operation(&app_handle, &id, &finished, &install_dir, &cancel, writes_config).await else {
+            return;
+        };
+
+        let mut sink = EventSink { app_handle: app_handle.clone(), event: format!("cli-output-{}", event_id) };
+        sink.line(OutputStream::Stdout, &format!("Starting: {:?}", args));
+
+        let (status, exit_code) = match parsed {
+            Ok(action) => {
+                let progress = track_repo_progress(&app_handle, &action, &install_dir, &event_id);
+                let outcome = tokio::select! {
+                    success = cli_action::dispatch(&action, &install_dir, cfg, &mut sink) => Some(success),
+                    _ = cancel.cancelled() => None,
+                };
+                drop(progress);
+
+                // Refresh config from disk to pick persisted changes if any
+                if let Err(e) = state.config.reload(&install_dir) {
+                    log::warn!("Failed to reload config: {}", e);
+                }
 
-    emit_line("stdout", format!("Starting: {:?}", args));
-
-    let mut success = true;
-    let mut exit_code: Option<i32> = Some(0);
-
-    if args.contains(&"--setup-env".to_string()) {
-        emit_line("stdout", "Setting up environment...".into());
-        let _ = add_log_entry(
-            state.clone(),
-            app_handle.clone(),
-            "info".to_string(),
-            "CLI".to_string(),
-            "Starting environment setup".to_string(),
-            Some("cli".to_string()),
-        ).await;
-        
-        let env_mgr = PsEnvManager::new(install_dir.clone());
-        if let Err(e) = env_mgr.setup_environment().await { 
-            success = false; 
-            exit_code = Some(1); 
-            emit_line("stderr", e.to_string());
-            let _ = add_log_entry(
-                state.clone(),
-                app_handle.clone(),
-                "error".to_string(),
-                "CLI".to_string(),
-                format!("Environment setup failed: {}", e),
-                Some("cli".to_string()),
-            ).await;
-        } else { 
-            emit_line("stdout", "Environment setup completed".into());
-            let _ = add_log_entry(
-                state.clone(),
-                app_handle.clone(),
-                "info".to_string(),
-                "CLI".to_string(),
-                "Environment setup completed successfully".to_string(),
-                Some("cli".to_string()),
-            ).await;
-        }
-    } else if let Some(pos) = args.iter().position(|a| a == "--install-repo") {
-        if let Some(repo) = args.get(pos + 1) {
-            emit_line("stdout", format!("Installing repo '{}'...", repo));
-            let _ = add_log_entry(
-                state.clone(),
-                app_handle.clone(),
-                "info".to_string(),
-                "CLI".to_string(),
-                format!("Installing repository: {}", repo),
-                Some("repository".to_string()),
-            ).await;
-            
-            let mut installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-            if let Err(e) = installer.install_repository(repo).await { 
-                success = false; 
-                exit_code = Some(1); 
-                emit_line("stderr", e.to_string());
-                let _ = add_log_entry(
-                    state.clone(),
-                    app_handle.clone(),
-                    "error".to_string(),
-                    "CLI".to_string(),
-                    format!("Repository installation failed: {}", e),
-                    Some("repository".to_string()),
-                ).await;
-            } else { 
-                emit_line("stdout", "Repository installed".into());
-                let _ = add_log_entry(
-                    state.clone(),
-                    app_handle.clone(),
-                    "info".to_string(),
-                    "CLI".to_string(),
-                    format!("Repository '{}' installed successfully", repo),
-                    Some("repository".to_string()),
-                ).await;
+                match outcome {
+                    Some(true) => (JobState::Succeeded, Some(0)),
+                    Some(false) => (JobState::Failed, Some(1)),
+                    None => {
+                        // The library does not expose the processes it starts; dropping its future is all cancel can do
+                        sink.line(OutputStream::Stderr, "Cancelled (tools the installer already started may still run to completion)");
+                        (JobState::Cancelled, None)
+                    }
+                }
             }
-        } else { success = false; exit_code = Some(1); emit_line("stderr", "Missing repository name".into()); }
-    } else if let Some(pos) = args.iter().position(|a| a == "--update-repo") {
-        if let Some(repo) = args.get(pos + 1) {
-            emit_line("stdout", format!("Updating repo '{}'...", repo));
-            let mut installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-            if let Err(e) = installer.update_repository(repo).await { success = false; exit_code = Some(1); emit_line("stderr", e.to_string()); }
-            else { emit_line("stdout", "Repository updated".into()); }
-        } else { success = false; exit_code = Some(1); emit_line("stderr", "Missing repository name".into()); }
-    } else if let Some(pos) = args.iter().position(|a| a == "--delete-repo") {
-        if let Some(repo) = args.get(pos + 1) {
-            emit_line("stdout", format!("Deleting repo '{}'...", repo));
-            let installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-            if let Err(e) = installer.delete_repository(repo) { success = false; exit_code = Some(1); emit_line("stderr", e.to_string()); }
-            else { emit_line("stdout", "Repository deleted".into()); }
-        } else { success = false; exit_code = Some(1); emit_line("stderr", "Missing repository name".into()); }
-    } else if args.contains(&"--version".to_string()) {
-        emit_line("stdout", format!("PortableSource version: {}", portablesource_rs::config::VERSION));
-    } else if args.contains(&"--check-env".to_string()) {
-        let env_mgr = PsEnvManager::new(install_dir.clone());
-        match env_mgr.check_environment_status() {
-            Ok(status) => {
-                emit_line("stdout", format!("Environment exists: {}", if status { "YES" } else { "NO" }));
-                emit_line("stdout", format!("Setup completed: {}", if cfg.is_environment_setup_completed() { "YES" } else { "NO" }));
+            Err(e) => {
+                sink.line(OutputStream::Stderr, &e.to_string());
+                (JobState::Failed, Some(2))
             }
-            Err(e) => { success = false; exit_code = Some(1); emit_line("stderr", e.to_string()); }
-        }
-    } else {
-        success = false;
-        exit_code = Some(1);
-        emit_line("stderr", "Unsupported command arguments".into());
-    }
+        };
 
-    *state.config.lock().map_err(|_| "State poisoned")? = cfg;
-    let _ = app_handle.emit(
-        &format!("cli-finished-{}", event_id),
-        StreamFinished { success, exit_code },
-    );
-    Ok(())
+        finish_job(&app_handle, &id, &finished, status, exit_code, None);
+    });
+
+    Ok(job_id)
 }
 
 #[tauri::command]
@@ -518,137 +662,336 @@ async fn setup_environment_stream(
     state: tauri::State<'_, AppState>,
     install_path: String,
     event_id: String,
-) -> Result<(), String> {
+    ignore_disk_space: Option<bool>,
+) -> Result<String, String> {
     log::info!("setup_environment_stream(install_path={}, event_id={})", install_path, event_id);
-    
+
     // Log to console if enabled
-    let _ = add_log_entry(
-        state.clone(),
-        app_handle.clone(),
-        "info".to_string(),
-        "GUI".to_string(),
+    let _ = push_log_entry(
+        &app_handle,
+        LogLevel::Info,
+        "GUI",
         format!("Starting environment setup at: {}", install_path),
         Some("environment".to_string()),
-    ).await;
-    
-    let install_dir = std::path::PathBuf::from(&install_path);
-    let mut cfg = state.config.lock().map_err(|_| "State poisoned")?.clone();
-    if cfg.get_config().install_path.as_os_str().is_empty() {
-        let _ = cfg.set_install_path(install_dir.clone());
-    }
-    let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg.clone());
+    );
 
-    let app = app_handle.clone();
-    let ev_id = event_id.clone();
-    let app_progress = app.clone();
-    let ev_progress = ev_id.clone();
-    
+    let install_dir = validated_install_dir(&app_handle, &install_path).await?;
+    ensure_disk_space(&app_handle, &install_dir, DiskAction::SetupEnv, ignore_disk_space.unwrap_or(false)).await?;
+
+    let (job_id, cancel) = state.jobs.create("setup-env", &event_id);
+    let id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let finished = format!("env-setup-finished-{}", event_id);
+        let Some((cfg, _operation)) = start_job_operation(&app_handle, &id, &finished, &install_dir, &cancel, true).await else {
+            return;
+        };
+        let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg);
+
+        let app_progress = app_handle.clone();
+        let ev_progress = event_id.clone();
+
+        let emit = move |phase: String, done: usize, total: usize| {
+            let _ = app_progress.emit(
+                &format!("env-setup-progress-{}", ev_progress),
+                UiProgressEvent { phase: phase.clone(), done, total },
+            );
+
+            // Note: Progress logging removed due to lifetime constraints
+            // Progress is still logged via the main success/error handlers
+        };
+
+        let outcome = tokio::select! {
+            result = env_mgr.setup_environment_with_progress(emit) => Some(result),
+            _ = cancel.cancelled() => None,
+        };
+
+        let (status, error) = match outcome {
+            Some(Ok(_)) => {
+                // Log success to console
+                let _ = push_log_entry(
+                    &app_handle,
+                    LogLevel::Info,
+                    "CLI",
+                    "Environment setup completed successfully".to_string(),
+                    Some("environment".to_string()),
+                );
+                (JobState::Succeeded, None)
+            }
+            Some(Err(e)) => {
+                let _ = app_handle.emit(
+                    &format!("env-setup-error-{}", event_id),
+                    e.to_string(),
+                );
+
+                // Log error to console
+                let _ = push_log_entry(
+                    &app_handle,
+                    LogLevel::Error,
+                    "CLI",
+                    format!("Environment setup failed: {}", e),
+                    Some("environment".to_string()),
+                );
+                (JobState::Failed, Some(e.to_string()))
+            }
+            None => {
+                let _ = push_log_entry(
+                    &app_handle,
+                    LogLevel::Warn,
+                    "GUI",
+                    "Environment setup cancelled (installers it already started may still run to completion)".to_string(),
+                    Some("environment".to_string()),
+                );
+                (JobState::Cancelled, None)
+            }
+        };
+
+        // Reload config so in-memory state matches file after setup
+        if let Err(e) = state.config.reload(&install_dir) {
+            log::warn!("Failed to reload config: {}", e);
+        }
+
+        let exit_code = match status {
+            JobState::Succeeded => Some(0),
+            JobState::Cancelled => None,
+            _ => Some(1),
+        };
+        finish_job(&app_handle, &id, &finished, status, exit_code, error);
+    });
+
+    Ok(job_id)
+}
+
+// Reinstalls only the tools diagnose_environment reports as missing, broken or outdated; working ones are not touched.
+// Runs as a setup-env job, so progress and completion arrive on the same events as setup_environment_stream.
+#[tauri::command]
+async fn repair_environment(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    install_path: String,
+    tools: Option<Vec<Tool>>,
+    event_id: String,
+    ignore_disk_space: Option<bool>,
+) -> Result<String, String> {
+    log::info!("repair_environment(install_path={}, tools={:?}, event_id={})", install_path, tools, event_id);
+    let install_dir = InstallRoot::new(&install_path)?.path().to_path_buf();
+    let diagnosis = env_doctor::diagnose(&install_dir).await;
+    let targets = env_doctor::repair_targets(&diagnosis, tools.as_deref().unwrap_or_default());
+
+    // The damaged folders still exist, so the usual setup estimate would not count them
+    let components: Vec<_> = targets.iter().map(|tool| tool.disk_component()).collect();
+    let preflight = {
+        let install_dir = install_dir.clone();
+        tauri::async_runtime::spawn_blocking(move || disk_space::preflight(&install_dir, &components))
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    accept_preflight(&app_handle, &preflight, ignore_disk_space.unwrap_or(false))?;
+
+    let (job_id, cancel) = state.jobs.create("setup-env", &event_id);
+    let id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let finished = format!("env-setup-finished-{}", event_id);
+        let Some((cfg, _operation)) = start_job_operation(&app_handle, &id, &finished, &install_dir, &cancel, true).await else {
+            return;
+        };
+
+        let outcome = if targets.is_empty() {
+            let _ = push_log_entry(&app_handle, LogLevel::Info, "GUI", "Environment repair: all tools are working".to_string(), Some("environment".to_string()));
+            let _ = app_handle.emit(&format!("env-setup-progress-{}", event_id), UiProgressEvent { phase: "done".to_string(), done: 0, total: 0 });
+            Some(Ok(()))
+        } else {
+            tokio::select! {
+                result = repair_tools(&app_handle, &install_dir, cfg, &targets, &event_id) => Some(result),
+                _ = cancel.cancelled() => None,
+            }
+        };
+
+        let (status, error) = match outcome {
+            Some(Ok(())) => {
+                if !targets.is_empty() {
+                    let _ = push_log_entry(&app_handle, LogLevel::Info, "CLI", format!("Environment repair completed: {:?}", targets), Some("environment".to_string()));
+                }
+                (JobState::Succeeded, None)
+            }
+            Some(Err(e)) => {
+                let _ = app_handle.emit(&format!("env-setup-error-{}", event_id), e.clone());
+                let _ = push_log_entry(&app_handle, LogLevel::Error, "CLI", format!("Environment repair failed: {}", e), Some("environment".to_string()));
+                (JobState::Failed, Some(e))
+            }
+            None => {
+                let _ = push_log_entry(&app_handle, LogLevel::Warn, "GUI", "Environment repair cancelled".to_string(), Some("environment".to_string()));
+                (JobState::Cancelled, None)
+            }
+        };
+
+        if let Err(e) = state.config.reload(&install_dir) {
+            log::warn!("Failed to reload config: {}", e);
+        }
+
+        let exit_code = match status {
+            JobState::Succeeded => Some(0),
+            JobState::Cancelled => None,
+            _ => Some(1),
+        };
+        finish_job(&app_handle, &id, &finished, status, exit_code, error);
+    });
+
+    Ok(job_id)
+}
+
+async fn repair_tools(app_handle: &tauri::AppHandle, install_dir: &Path, cfg: PsConfigManager, targets: &[Tool], event_id: &str) -> Result<(), String> {
+    // Dropped with this future on error or cancel, which puts the old folders back
+    let set_aside = env_doctor::SetAside::move_aside(install_dir, targets)?;
+    let _ = push_log_entry(app_handle, LogLevel::Info, "GUI", format!("Reinstalling {:?}", targets), Some("environment".to_string()));
+
+    // With the damaged folders out of the way, setup installs those tools and skips the rest
+    let env_mgr = PsEnvManager::with_config(install_dir.to_path_buf(), cfg);
+    let app_progress = app_handle.clone();
+    let event = format!("env-setup-progress-{}", event_id);
     let emit = move |phase: String, done: usize, total: usize| {
-        let _ = app_progress.emit(
-            &format!("env-setup-progress-{}", ev_progress),
-            UiProgressEvent { phase: phase.clone(), done, total },
-        );
-        
-        // Note: Progress logging removed due to lifetime constraints
-        // Progress is still logged via the main success/error handlers
+        let _ = app_progress.emit(&event, UiProgressEvent { phase, done, total });
     };
+    env_mgr.setup_environment_with_progress(emit).await.map_err(|e| e.to_string())?;
 
-    let mut success = true;
-    if let Err(e) = env_mgr
-        .setup_environment_with_progress(move |phase, done, total| emit(phase, done, total))
-        .await
-    {
-        success = false;
-        let _ = app_handle.emit(
-            &format!("env-setup-error-{}", event_id),
-            e.to_string(),
-        );
-        
-        // Log error to console
-        let _ = add_log_entry(
-            state.clone(),
-            app_handle.clone(),
-            "error".to_string(),
-            "CLI".to_string(),
-            format!("Environment setup failed: {}", e),
-            Some("environment".to_string()),
-        ).await;
-    } else {
-        // Log success to console
-        let _ = add_log_entry(
-            state.clone(),
-            app_handle.clone(),
-            "info".to_string(),
-            "CLI".to_string(),
-            "Environment setup completed successfully".to_string(),
-            Some("environment".to_string()),
-        ).await;
-    }
-    
-    let _ = app_handle.emit(
-        &format!("env-setup-finished-{}", event_id),
-        StreamFinished { success, exit_code: Some(if success { 0 } else { 1 }) },
-    );
-    
-    // Reload config so in-memory state matches file after setup
-    if let Ok(updated) = PsConfigManager::new(None) {
-        *state.config.lock().map_err(|_| "State poisoned")? = updated;
-    } else {
-        *state.config.lock().map_err(|_| "State poisoned")? = cfg;
-    }
+    env_doctor::verify_repaired(&env_doctor::diagnose(install_dir).await, targets)
+        .map_err(|e| format!("{}. The previous folders were put back", e))?;
+    set_aside.commit();
     Ok(())
 }
 
 #[tauri::command]
-async fn run_batch_in_new_window(batch_file: String, working_dir: String) -> Result<CommandResult, String> {
+async fn cancel_job(state: tauri::State<'_, AppState>, job_id: String) -> Result<JobInfo, String> {
+    log::info!("cancel_job(job_id={})", job_id);
+    state.jobs.cancel(&job_id)
+}
+
+#[tauri::command]
+async fn get_job(state: tauri::State<'_, AppState>, job_id: String) -> Result<JobInfo, String> {
+    state.jobs.get(&job_id).ok_or_else(|| format!("Unknown job '{}'", job_id))
+}
+
+#[tauri::command]
+async fn list_jobs(state: tauri::State<'_, AppState>) -> Result<Vec<JobInfo>, String> {
+    Ok(state.jobs.list())
+}
+
+#[tauri::command]
+async fn run_batch_in_new_window(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    batch_file: String,
+    working_dir: String,
+) -> Result<CommandResult, String> {
     if cfg!(target_os = "windows") {
         // Use full path to batch file to avoid caching issues
-        let full_batch_path = Path::new(&working_dir).join(&batch_file);
-        let full_batch_path_str = full_batch_path.to_string_lossy();
-        
-        // Use start command to open batch file in new console window with full path
-        let output = Command::new("cmd")
-            .args(["/C", "start", "cmd", "/K", &full_batch_path_str])
-            .current_dir(&working_dir)
-            .output()
-            .map_err(|e| format!("Failed to run batch file in new window: {}", e))?;
-        
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let exit_code = output.status.code();
-        
-        Ok(CommandResult {
-            success: output.status.success(),
-            stdout,
-            stderr,
-            exit_code,
-        })
+        let full_batch_path = InstallRoot::new(&working_dir)?.resolve(paths::file_name(&batch_file)?)?;
+
+        // start_<repo>.bat lives in repos/<repo>; either name identifies the supervised process
+        let repo = batch_file
+            .strip_prefix("start_")
+            .and_then(|s| s.strip_suffix(".bat"))
+            .map(|s| s.to_string())
+            .or_else(|| Path::new(&working_dir).file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_else(|| batch_file.clone());
+
+        let spec = LaunchSpec {
+            program: "cmd".to_string(),
+            args: vec!["/C".to_string(), full_batch_path.to_string_lossy().to_string()],
+            working_dir: PathBuf::from(&working_dir),
+            env: Vec::new(),
+        };
+
+        match state.supervisor.start(&app_handle, &repo, spec) {
+            Ok(running) => Ok(CommandResult {
+                success: true,
+                stdout: format!("Started '{}' (pid {})", running.repo, running.pid),
+                stderr: String::new(),
+                exit_code: None,
+            }),
+            Err(e) => Ok(CommandResult { success: false, stdout: String::new(), stderr: e, exit_code: None }),
+        }
     } else {
         Err("This function is only supported on Windows".to_string())
     }
 }
 
+#[tauri::command]
+async fn launch_repository(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    install_path: String,
+    repo: String,
+) -> Result<LaunchResult, String> {
+    log::info!("launch_repository(install_path={}, repo={})", install_path, repo);
+    let root = InstallRoot::new(&install_path)?;
+    root.repo_dir(&RepoName::parse(&repo)?)?;
+    let resolved = launcher::resolve_launcher(root.path(), &repo)?;
+    let launcher = resolved.script.to_string_lossy().to_string();
+
+    match state.supervisor.start(&app_handle, &repo, resolved.spec) {
+        Ok(running) => Ok(LaunchResult {
+            success: true,
+            repo,
+            pid: Some(running.pid),
+            launcher,
+            generated: resolved.generated,
+            message: format!("Started '{}' (pid {})", running.repo, running.pid),
+        }),
+        Err(e) => Ok(LaunchResult { success: false, repo, pid: None, launcher, generated: resolved.generated, message: e }),
+    }
+}
+
+#[tauri::command]
+async fn list_running_repos(state: tauri::State<'_, AppState>) -> Result<Vec<RunningRepo>, String> {
+    Ok(state.supervisor.list())
+}
+
+#[tauri::command]
+async fn stop_repo(state: tauri::State<'_, AppState>, repo_name: String) -> Result<(), String> {
+    log::info!("stop_repo(repo_name={})", repo_name);
+    state.supervisor.stop(&repo_name).await
+}
+
+#[tauri::command]
+async fn restart_repo(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>, repo_name: String) -> Result<RunningRepo, String> {
+    log::info!("restart_repo(repo_name={})", repo_name);
+    state.supervisor.restart(&app_handle, &repo_name).await
+}
+
+#[tauri::command]
+async fn get_repo_output(state: tauri::State<'_, AppState>, repo_name: String, since: Option<u64>) -> Result<Vec<RepoOutputLine>, String> {
+    Ok(state.supervisor.output(&repo_name, since))
+}
+
 #[tauri::command]
 async fn check_environment_installed(install_path: String) -> Result<bool, String> {
-    let conda_path = Path::new(&install_path).join("miniconda").join("_conda.exe");
+    let conda_path = InstallRoot::new(&install_path)?.resolve("miniconda/_conda.exe")?;
     Ok(conda_path.exists())
 }
 
 #[tauri::command]
 async fn check_repository_installed(install_path: String, repo_name: String) -> Result<bool, String> {
-    let repo_path = Path::new(&install_path).join("repos").join(&repo_name);
+    let repo_path = InstallRoot::new(&install_path)?.repo_dir(&RepoName::parse(&repo_name)?)?;
     Ok(repo_path.exists() && repo_path.is_dir())
 }
 
+// `path` is relative to the install directory, e.g. "ps_env/python/python.exe"
 #[tauri::command]
-async fn file_exists(path: String) -> Result<bool, String> {
-    let file_path = Path::new(&path);
+async fn file_exists(install_path: String, path: String) -> Result<bool, String> {
+    let file_path = InstallRoot::new(&install_path)?.resolve(&path)?;
     Ok(file_path.exists() && file_path.is_file())
 }
 
+#[tauri::command]
+async fn list_installed_repositories(install_path: String) -> Result<Vec<InstalledRepository>, String> {
+    installed_repos::list(InstallRoot::new(&install_path)?.path())
+}
+
 #[tauri::command]
 async fn list_directory_folders(install_path: String, directory_name: String) -> Result<Vec<String>, String> {
-    let dir_path = Path::new(&install_path).join(&directory_name);
+    let dir_path = InstallRoot::new(&install_path)?.resolve(&directory_name)?;
     
     if !dir_path.exists() || !dir_path.is_dir() {
         return Ok(vec![]);
@@ -731,14 +1074,15 @@ struct EnvironmentStatus {
 #[tauri::command]
 async fn check_environment_status(state: tauri::State<'_, AppState>, install_path: String) -> Result<EnvironmentStatus, String> {
     //log::info!("check_environment_status(install_path={})", install_path);
-    let install_dir = PathBuf::from(&install_path);
-    let mut cfg = state.config.lock().map_err(|_| "State poisoned")?.clone();
-    let env_mgr = PsEnvManager::with_config(install_dir.clone(), cfg.clone());
-    let environment_exists = env_mgr.check_environment_status().map_err(|e| e.to_string())?;
-    if cfg.get_config().install_path.as_os_str().is_empty() {
-        let _ = cfg.set_install_path(install_dir.clone());
-    }
+    let install_dir = InstallRoot::new(&install_path)?.path().to_path_buf();
+    let cfg = state.config.for_operation(&install_dir)?;
+    environment_status(&install_dir, cfg)
+}
+
+fn environment_status(install_dir: &Path, cfg: PsConfigManager) -> Result<EnvironmentStatus, String> {
     let setup_completed = cfg.is_environment_setup_completed();
+    let env_mgr = PsEnvManager::with_config(install_dir.to_path_buf(), cfg);
+    let environment_exists = env_mgr.check_environment_status().map_err(|e| e.to_string())?;
     let overall_status = if setup_completed {
         "Ready".to_string()
     } else if environment_exists {
@@ -746,10 +1090,16 @@ async fn check_environment_status(state: tauri::State<'_, AppState>, install_pat
     } else {
         "Environment not found".to_string()
     };
-    *state.config.lock().map_err(|_| "State poisoned")? = cfg;
     Ok(EnvironmentStatus { environment_exists, setup_completed, overall_status })
 }
 
+#[tauri::command]
+async fn diagnose_environment(install_path: String) -> Result<EnvironmentDiagnosis, String> {
+    log::info!("diagnose_environment(install_path={})", install_path);
+    let root = InstallRoot::new(&install_path)?;
+    Ok(env_doctor::diagnose(root.path()).await)
+}
+
 #[tauri::command]
 async fn clear_install_path() -> Result<InstallResult, String> {
     // Since we no longer use registry, this function just returns success
@@ -762,7 +1112,7 @@ async fn clear_install_path() -> Result<InstallResult, String> {
 
 #[tauri::command]
 async fn check_environment_exists_at_path(install_path: String) -> Result<bool, String> {
-    let install_dir = PathBuf::from(&install_path);
+    let install_dir = InstallRoot::new(&install_path)?.path().to_path_buf();
     let cfg = PsConfigManager::new(Some(install_dir.clone())).map_err(|e| e.to_string())?;
     let env_mgr = PsEnvManager::with_config(install_dir, cfg);
     
@@ -777,23 +1127,34 @@ async fn delete_repository(state: tauri::State<'_, AppState>, install_path: Stri
     log::info!("[tauri] delete_repository called: install_path={:?}, repo_name={:?}", install_path, repo_name);
     let repo_name_for_message = repo_name.clone().unwrap_or_default();
     if let Some(repo) = repo_name {
-        let mut cfg = state.config.lock().map_err(|_| "State poisoned")?.clone();
-        let install_dir = PathBuf::from(&install_path);
-        if cfg.get_config().install_path.as_os_str().is_empty() {
-            let _ = cfg.set_install_path(install_dir.clone());
-        }
-        let installer = PsRepoInstaller::new(install_dir.clone(), cfg.clone());
-        let result = installer.delete_repository(&repo);
+        let root = InstallRoot::new(&install_path)?;
+        let repo = RepoName::parse(&repo)?;
+        let paths = repo_removal::repository_paths(&root, &repo)?;
+        let install_dir = root.path().to_path_buf();
+        let (cfg, _operation) = state.config.begin_operation(&install_dir).await?;
+        let installer = PsRepoInstaller::new(install_dir.clone(), cfg);
+        let result = installer.delete_repository(repo.as_str());
+        let mut problems = Vec::new();
         if result.is_ok() {
-            // Try to remove folders from envs/ and repos/
-            let envs = install_dir.join("envs").join(&repo);
-            let repos = install_dir.join("repos").join(&repo);
-            let _ = std::fs::remove_dir_all(&envs);
-            let _ = std::fs::remove_dir_all(&repos);
+            // Remove folders from envs/ and repos/, keeping track of what could not be deleted
+            let removals = tauri::async_runtime::spawn_blocking(move || paths.iter().map(|p| repo_removal::remove_path(p)).collect::<Vec<_>>())
+                .await
+                .map_err(|e| e.to_string())?;
+            problems.extend(removals.iter().filter_map(|r| r.problem()));
+            if let Err(e) = installed_repos::forget(&install_dir, repo.as_str()) {
+                log::warn!("{}", e);
+            }
+        }
+        if let Err(e) = state.config.reload(&install_dir) {
+            log::warn!("Failed to reload config: {}", e);
         }
-        *state.config.lock().map_err(|_| "State poisoned")? = cfg;
         match result {
-            Ok(_) => Ok(InstallResult { success: true, message: format!("Repository '{}' deleted successfully", repo_name_for_message), normalized_path: None }),
+            Ok(_) if problems.is_empty() => Ok(InstallResult { success: true, message: format!("Repository '{}' deleted successfully", repo_name_for_message), normalized_path: None }),
+            Ok(_) => Ok(InstallResult {
+                success: false,
+                message: format!("Repository '{}' was removed from the config, but some files remain: {}", repo_name_for_message, problems.join("; ")),
+                normalized_path: None,
+            }),
             Err(e) => Ok(InstallResult { success: false, message: format!("Failed to delete repository: {}", e), normalized_path: None }),
         }
     } else {
@@ -801,6 +1162,72 @@ async fn delete_repository(state: tauri::State<'_, AppState>, install_path: Stri
     }
 }
 
+// Under the installation's operation lock: stops the repo if it runs, deletes its folders,
+// then lets the installer drop its config entries
+async fn remove_repository_into(state: &AppState, root: &InstallRoot, repo: &RepoName, report: &mut RemovalReport) -> Result<(), String> {
+    let paths = repo_removal::repository_paths(root, repo)?;
+    let install_dir = root.path();
+    let repo = repo.as_str();
+    // Held across the delete so an install or update cannot write into the folders meanwhile
+    let (cfg, _operation) = state.config.begin_operation(install_dir).await?;
+    if state.supervisor.list().iter().any(|r| r.repo == repo) {
+        if let Err(e) = state.supervisor.stop(repo).await {
+            report.warnings.push(e);
+        }
+    }
+
+    let removals = tauri::async_runtime::spawn_blocking(move || paths.iter().map(|p| repo_removal::remove_path(p)).collect::<Vec<_>>())
+        .await
+        .map_err(|e| e.to_string())?;
+    for removal in removals {
+        report.push(removal);
+    }
+
+    let installer = PsRepoInstaller::new(install_dir.to_path_buf(), cfg);
+    if let Err(e) = installer.delete_repository(repo) {
+        report.warnings.push(format!("Config cleanup for '{}': {}", repo, e));
+    }
+    if let Err(e) = installed_repos::forget(install_dir, repo) {
+        report.warnings.push(e);
+    }
+    if let Err(e) = state.config.reload(install_dir) {
+        report.warnings.push(format!("Config reload: {}", e));
+    }
+
+    report.repos.push(repo.to_string());
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_repository_artifacts(state: tauri::State<'_, AppState>, install_path: String, repo: String) -> Result<RemovalReport, String> {
+    log::info!("remove_repository_artifacts(install_path={}, repo={})", install_path, repo);
+    let root = InstallRoot::new(&install_path)?;
+    let repo = RepoName::parse(&repo)?;
+    let mut report = RemovalReport::new();
+    remove_repository_into(&state, &root, &repo, &mut report).await?;
+    Ok(report)
+}
+
+#[tauri::command]
+async fn remove_all_repositories(state: tauri::State<'_, AppState>, install_path: String) -> Result<RemovalReport, String> {
+    log::info!("remove_all_repositories(install_path={})", install_path);
+    let root = InstallRoot::new(&install_path)?;
+    let mut report = RemovalReport::new();
+    for name in repo_removal::all_repository_names(root.path()) {
+        // Hand-made folders may have unusable names or link outside the install; leave them alone
+        let checked = RepoName::parse(&name).and_then(|repo| repo_removal::repository_paths(&root, &repo).map(|_| repo));
+        match checked {
+            Ok(repo) => {
+                if let Err(e) = remove_repository_into(&state, &root, &repo, &mut report).await {
+                    report.fail(format!("'{}': {}", name, e));
+                }
+            }
+            Err(e) => report.warnings.push(format!("Skipped '{}': {}", name, e)),
+        }
+    }
+    Ok(report)
+}
+
 #[tauri::command]
 async fn get_cli_version(_state: tauri::State<'_, AppState>, install_path: String) -> Result<String, String> {
     // Log to ensure dev build picks new signature
@@ -870,28 +1297,27 @@ async fn check_for_updates(app_handle: tauri::AppHandle) -> Result<serde_json::V
     }
 }
 
+// Downloads and installs right away; uses the staged package when it is current
 #[tauri::command]
 async fn install_update(app_handle: tauri::AppHandle) -> Result<(), String> {
-    use tauri_plugin_updater::UpdaterExt;
-    
-    match app_handle.updater() {
-        Ok(updater) => {
-            match updater.check().await {
-                Ok(update) => {
-                    if let Some(update) = update {
-                        match update.download_and_install(|_chunk_length, _content_length| {}, || {}).await {
-                            Ok(_) => Ok(()),
-                            Err(e) => Err(format!("Failed to install update: {}", e))
-                        }
-                    } else {
-                        Err("No update available".to_string())
-                    }
-                },
-                Err(e) => Err(format!("Failed to check for updates: {}", e))
-            }
-        },
-        Err(e) => Err(format!("Updater not available: {}", e))
-    }
+    updates::install_now(&app_handle).await
+}
+
+// Downloads and verifies the update now; it is installed on the next launch
+#[tauri::command]
+async fn download_update(app_handle: tauri::AppHandle) -> Result<StagedUpdate, String> {
+    updates::download_and_stage(&app_handle).await
+}
+
+#[tauri::command]
+async fn get_staged_update(app_handle: tauri::AppHandle) -> Result<Option<StagedUpdate>, String> {
+    Ok(updates::staged(&app_handle))
+}
+
+#[tauri::command]
+async fn discard_staged_update(app_handle: tauri::AppHandle) -> Result<(), String> {
+    updates::discard(&app_handle);
+    Ok(())
 }
 
 // --- MSVC Build Tools support & admin check ---
@@ -975,21 +1401,18 @@ fn get_app_version() -> String {
 }
 
 #[tauri::command]
-async fn is_first_run() -> Result<bool, String> {
-    // Проверяем, есть ли папка ps_env рядом с исполняемым файлом
-    let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
-    let exe_dir = exe_path.parent().ok_or("Cannot get executable directory")?;
-    let ps_env_path = exe_dir.join("ps_env");
-    
-    Ok(!ps_env_path.exists())
+async fn is_first_run(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    // Ни одной установки: ни в реестре, ни рядом с исполняемым файлом
+    Ok(active_installation(&state).is_none())
 }
 
 #[tauri::command]
-async fn copy_self_to_install_path(install_path: String) -> Result<InstallResult, String> {
+async fn copy_self_to_install_path(app_handle: tauri::AppHandle, install_path: String) -> Result<InstallResult, String> {
     let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
     let exe_name = exe_path.file_name().ok_or("Cannot get executable name")?;
     
-    let install_dir = Path::new(&install_path);
+    let install_dir = validated_install_dir(&app_handle, &install_path).await?;
+    let install_dir = install_dir.as_path();
     let target_path = install_dir.join(exe_name);
     
     // Создаем директорию установки если её нет
@@ -1014,11 +1437,11 @@ async fn copy_self_to_install_path(install_path: String) -> Result<InstallResult
 }
 
 #[tauri::command]
-async fn complete_uninstall(state: tauri::State<'_, AppState>) -> Result<InstallResult, String> {
+async fn complete_uninstall(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<InstallResult, String> {
     // First, get the install path
-    let install_path = match get_install_path().await {
-        Ok(path) => path,
-        Err(_) => {
+    let install_path = match active_installation(&state) {
+        Some(path) => path.to_string_lossy().to_string(),
+        None => {
             return Ok(InstallResult {
                 success: false,
                 message: "Installation path not found in registry".to_string(),
@@ -1027,18 +1450,34 @@ async fn complete_uninstall(state: tauri::State<'_, AppState>) -> Result<Install
         }
     };
     
-    let install_dir = Path::new(&install_path);
+    // Never hand a relative or ".."-laden path to remove_dir_all
+    let root = match InstallRoot::new(&install_path) {
+        Ok(root) => root,
+        Err(e) => return Ok(InstallResult { success: false, message: e.to_string(), normalized_path: None }),
+    };
+    let install_dir = root.path();
     // Шаг 1: Очистить ключи реестра (без вызова внешнего EXE)
     let _ = clear_install_path().await;
     
     // Step 2: Remove the entire installation directory
     if install_dir.exists() {
+        // The watcher holds handles on the folders being removed
+        follow_installation(&app_handle, &state, None);
         match fs::remove_dir_all(&install_dir) {
             Ok(_) => {
                 // Step 3: Clear registry entry (повторно на всякий случай)
                 let _ = clear_install_path().await;
-                // Reset in-memory config
-                *state.config.lock().map_err(|_| "State poisoned")? = PsConfigManager::new(None).map_err(|e| e.to_string())?;
+                let next = match state.installations.lock() {
+                    Ok(mut registry) => {
+                        if registry.remove(install_dir).is_ok() {
+                            let _ = installations::save(&app_handle, &registry);
+                        }
+                        registry.active()
+                    }
+                    Err(_) => None,
+                };
+                // Reset in-memory config, or hand over to the next known installation
+                activate_installation(&app_handle, &state, next.as_deref())?;
                 Ok(InstallResult {
                     success: true,
                     message: "Thank you for using this software! =}".to_string(),
@@ -1046,6 +1485,7 @@ async fn complete_uninstall(state: tauri::State<'_, AppState>) -> Result<Install
                 })
             }
             Err(e) => {
+                follow_installation(&app_handle, &state, Some(install_dir));
                 Ok(InstallResult {
                     success: false,
                     message: format!("Failed to remove installation directory: {}", e),
@@ -1081,131 +1521,272 @@ async fn clear_console_logs(state: tauri::State<'_, AppState>) -> Result<(), Str
 
 #[tauri::command]
 async fn toggle_console(state: tauri::State<'_, AppState>, enabled: bool) -> Result<(), String> {
-    let mut console_enabled = state.console_enabled.lock().map_err(|_| "Console enabled state poisoned")?;
-    *console_enabled = enabled;
-    Ok(())
+    let settings = {
+        let mut settings = state.console_settings.lock().map_err(|_| "Console settings poisoned")?;
+        settings.enabled = enabled;
+        settings.clone()
+    };
+    persist_console_settings(&state, &settings)
 }
 
 #[tauri::command]
 async fn is_console_enabled(state: tauri::State<'_, AppState>) -> Result<bool, String> {
-    let console_enabled = state.console_enabled.lock().map_err(|_| "Console enabled state poisoned")?;
-    Ok(*console_enabled)
+    let settings = state.console_settings.lock().map_err(|_| "Console settings poisoned")?;
+    Ok(settings.enabled)
 }
 
-#[tauri::command]
-async fn add_log_entry(
-    state: tauri::State<'_, AppState>,
-    app_handle: tauri::AppHandle,
-    level: String,
-    source: String,
+// Install dir from the shared config, or the portable layout next to the executable
+fn default_install_dir(state: &AppState) -> Option<PathBuf> {
+    if let Some(path) = state.config.install_dir() {
+        return Some(path);
+    }
+    let exe = std::env::current_exe().ok()?;
+    let exe_dir = exe.parent()?;
+    exe_dir.join("ps_env").exists().then(|| exe_dir.to_path_buf())
+}
+
+// Appends an entry to the unified console buffer and forwards it to the frontend
+fn push_log_entry(
+    app_handle: &tauri::AppHandle,
+    level: LogLevel,
+    source: &str,
     message: String,
     module: Option<String>,
 ) -> Result<(), String> {
-    let console_enabled = {
-        let enabled = state.console_enabled.lock().map_err(|_| "Console enabled state poisoned")?;
-        *enabled
+    let state = app_handle.state::<AppState>();
+    let (enabled, max_entries) = {
+        let settings = state.console_settings.lock().map_err(|_| "Console settings poisoned")?;
+        if !settings.accepts(level) {
+            return Ok(());
+        }
+        (settings.enabled, settings.max_entries)
     };
-    
-    if !console_enabled {
-        return Ok(());
-    }
-    
+
     let entry = LogEntry {
         timestamp: Utc::now(),
-        level: level.clone(),
-        source: source.clone(),
-        message: message.clone(),
+        level,
+        source: source.to_string(),
+        message,
         module,
     };
-    
+
+    // Persist even while the console is hidden so failures can be inspected after a restart
+    if let Ok(mut sink) = state.log_files.lock() {
+        if sink.dir().is_none() {
+            if let Some(install_dir) = default_install_dir(&state) {
+                sink.set_install_dir(&install_dir);
+            }
+        }
+        sink.write(&entry);
+    }
+
+    if !enabled {
+        return Ok(());
+    }
+
     // Add to buffer
     {
         let mut buffer = state.log_buffer.lock().map_err(|_| "Log buffer poisoned")?;
         buffer.push_back(entry.clone());
-        
-        // Keep buffer size manageable (max 1000 entries)
-        if buffer.len() > 1000 {
+        while buffer.len() > max_entries {
             buffer.pop_front();
         }
     }
-    
+
     // Emit to frontend
     let _ = app_handle.emit("console-log", &entry);
-    
+
     Ok(())
 }
 
 #[tauri::command]
-async fn get_console_settings(state: tauri::State<'_, AppState>) -> Result<ConsoleSettings, String> {
-    let console_enabled = {
-        let enabled = state.console_enabled.lock().map_err(|_| "Console enabled state poisoned")?;
-        *enabled
+async fn add_log_entry(
+    app_handle: tauri::AppHandle,
+    level: String,
+    source: String,
+    message: String,
+    module: Option<String>,
+) -> Result<(), String> {
+    let level: LogLevel = level.parse()?;
+    push_log_entry(&app_handle, level, &source, message, module)
+}
+
+#[tauri::command]
+async fn load_console_history(state: tauri::State<'_, AppState>, limit: Option<usize>) -> Result<Vec<LogEntry>, String> {
+    let dir = {
+        let mut sink = state.log_files.lock().map_err(|_| "Log file sink poisoned")?;
+        if sink.dir().is_none() {
+            if let Some(install_dir) = default_install_dir(&state) {
+                sink.set_install_dir(&install_dir);
+            }
+        }
+        sink.dir().map(|d| d.to_path_buf())
     };
-    
-    Ok(ConsoleSettings {
-        enabled: console_enabled,
-        max_entries: 1000,
-        log_level: "info".to_string(),
-    })
+    match dir {
+        Some(dir) => Ok(log_files::read_history(&dir, limit.unwrap_or(1000))),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[tauri::command]
+async fn get_console_settings(state: tauri::State<'_, AppState>) -> Result<ConsoleSettings, String> {
+    let settings = state.console_settings.lock().map_err(|_| "Console settings poisoned")?;
+    Ok(settings.clone())
 }
 
 #[tauri::command]
 async fn set_console_settings(
     state: tauri::State<'_, AppState>,
     settings: ConsoleSettings,
-) -> Result<(), String> {
-    let mut console_enabled = state.console_enabled.lock().map_err(|_| "Console enabled state poisoned")?;
-    *console_enabled = settings.enabled;
-    Ok(())
+) -> Result<ConsoleSettings, String> {
+    let settings = settings.normalized();
+    *state.console_settings.lock().map_err(|_| "Console settings poisoned")? = settings.clone();
+
+    // Apply a smaller capacity right away instead of on the next entry
+    {
+        let mut buffer = state.log_buffer.lock().map_err(|_| "Log buffer poisoned")?;
+        while buffer.len() > settings.max_entries {
+            buffer.pop_front();
+        }
+    }
+
+    persist_console_settings(&state, &settings)?;
+    Ok(settings)
+}
+
+#[tauri::command]
+async fn get_control_api_status(state: tauri::State<'_, AppState>) -> Result<ControlApiStatus, String> {
+    state.control_api.status()
+}
+
+#[tauri::command]
+async fn set_control_api_settings(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    settings: ControlApiSettings,
+) -> Result<ControlApiStatus, String> {
+    log::info!("set_control_api_settings(enabled={}, port={})", settings.enabled, settings.port);
+    state.control_api.configure(&app_handle, settings)
+}
+
+// Saved under the install dir; before one is chosen the settings only live in memory
+fn persist_console_settings(state: &AppState, settings: &ConsoleSettings) -> Result<(), String> {
+    match default_install_dir(state) {
+        Some(install_dir) => console_settings::save(&install_dir, settings),
+        None => Ok(()),
+    }
+}
+
+// Runs a subcommand without a window when the arguments name one; None means start the GUI
+pub fn run_headless(args: &[String]) -> Option<i32> {
+    headless::run(args)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .manage(AppState { 
-            config: Mutex::new(PsConfigManager::new(None).unwrap_or_else(|_| PsConfigManager::new(Some(PathBuf::from("."))).expect("config init"))),
+            config: ConfigService::new(PsConfigManager::new(None).unwrap_or_else(|_| PsConfigManager::new(Some(PathBuf::from("."))).expect("config init"))),
             log_buffer: Arc::new(Mutex::new(VecDeque::new())),
-            console_enabled: Arc::new(Mutex::new(false)),
+            console_settings: Mutex::new(ConsoleSettings::default()),
+            log_files: Mutex::new(LogFileSink::new()),
+            jobs: JobRegistry::new(),
+            supervisor: Supervisor::new(),
+            catalog: CatalogClient::from_env(),
+            installations: Mutex::new(InstallationRegistry::default()),
+            install_watcher: InstallWatcher::default(),
+            control_api: ControlApi::default(),
         })
         .setup(|app| {
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+            // Library records reach the console in every build; debug builds also keep the plugin's output
+            let inner = if cfg!(debug_assertions) {
+                let (plugin, _, logger) = tauri_plugin_log::Builder::default()
+                    .level(log::LevelFilter::Info)
+                    .split(app.handle())?;
+                app.handle().plugin(plugin)?;
+                Some(logger)
+            } else {
+                None
+            };
+            log_bridge::install(app.handle().clone(), inner)?;
+            // Before installations load and any job can start: a staged update replaces this process
+            if updates::apply_staged_at_startup(app.handle()) {
+                // The installer took over and the app is exiting; start nothing else
+                return Ok(());
+            }
+            {
+                let state = app.state::<AppState>();
+                let registry = installations::load(app.handle());
+                if let Some(active) = registry.active() {
+                    if let Err(e) = state.config.switch_to(Some(&active)) {
+                        log::warn!("Failed to load config for {}: {}", active.display(), e);
+                    }
+                }
+                follow_installation(app.handle(), &state, state.config.install_dir().as_deref());
+                if let Ok(mut guard) = state.installations.lock() {
+                    *guard = registry;
+                }
+                if let Some(install_dir) = default_install_dir(&state) {
+                    if let Ok(mut settings) = state.console_settings.lock() {
+                        *settings = console_settings::load(&install_dir);
+                    }
+                }
             }
             app.handle().plugin(tauri_plugin_dialog::init())?;
             app.handle().plugin(tauri_plugin_updater::Builder::new().build())?;
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            validate_install_path,
+            check_disk_space,
             set_install_path,
             get_install_path,
             find_cli_installation,
+            list_installations,
+            add_installation,
+            remove_installation,
+            switch_installation,
             download_and_install_cli,
             run_cli_command,
             proxy_request,
+            catalog_top,
+            catalog_search,
             clear_install_path,
             check_environment_exists_at_path,
             delete_repository,
+            remove_repository_artifacts,
+            remove_all_repositories,
             complete_uninstall,
             check_environment_installed,
             check_environment_status,
+            diagnose_environment,
+            repair_environment,
             check_repository_installed,
             file_exists,
             list_directory_folders,
+            list_installed_repositories,
             run_command,
             run_command_stream,
             run_cli_command_stream,
             setup_environment_stream,
+            cancel_job,
+            get_job,
+            list_jobs,
             run_batch_in_new_window,
+            launch_repository,
+            list_running_repos,
+            stop_repo,
+            restart_repo,
+            get_repo_output,
             get_cli_version,
             get_latest_version_from_github,
             get_system_locale,
             get_app_version,
             check_for_updates,
             install_update,
+            download_update,
+            get_staged_update,
+            discard_staged_update,
             check_msvc_bt_installed,
             install_msvc_bt,
             is_admin,
@@ -1217,9 +1798,18 @@ pub fn run() {
             toggle_console,
             is_console_enabled,
             add_log_entry,
+            load_console_history,
             get_console_settings,
-            set_console_settings
+            set_console_settings,
+            get_control_api_status,
+            set_control_api_settings
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Launched repositories hold pipes to this process; take them down with it
+                app_handle.state::<AppState>().supervisor.stop_all();
+            }
+        });
 }
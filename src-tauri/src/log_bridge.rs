@@ -0,0 +1,67 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use tokio::sync::mpsc;
+
+use crate::console_settings::LogLevel;
+
+// Records from these crates are mirrored into the unified console
+const FORWARDED_TARGETS: &[&str] = &["portablesource_rs"];
+
+struct ForwardedRecord {
+    level: LogLevel,
+    module: Option<String>,
+    message: String,
+}
+
+// Global logger: hands library records to the console and everything to the wrapped logger (if any)
+struct ConsoleBridge {
+    tx: mpsc::UnboundedSender<ForwardedRecord>,
+    inner: Option<Box<dyn Log>>,
+}
+
+fn is_forwarded(target: &str) -> bool {
+    FORWARDED_TARGETS
+        .iter()
+        .any(|t| target == *t || target.strip_prefix(t).map(|rest| rest.starts_with("::")).unwrap_or(false))
+}
+
+impl Log for ConsoleBridge {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        is_forwarded(metadata.target()) || self.inner.as_ref().map(|l| l.enabled(metadata)).unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(inner) = &self.inner {
+            inner.log(record);
+        }
+        if !is_forwarded(record.target()) {
+            return;
+        }
+        // Queued rather than pushed inline: the caller may be holding the config lock push_log_entry needs
+        let _ = self.tx.send(ForwardedRecord {
+            level: record.level().into(),
+            module: Some(record.module_path().unwrap_or(record.target()).to_string()),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
+
+// Installs the bridge as the global logger; `inner` is the debug-build tauri_plugin_log logger
+pub(crate) fn install(app_handle: tauri::AppHandle, inner: Option<Box<dyn Log>>) -> Result<(), log::SetLoggerError> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<ForwardedRecord>();
+    log::set_boxed_logger(Box::new(ConsoleBridge { tx, inner }))?;
+    // Debug so a "debug" console threshold can see library records; push_log_entry applies the real threshold
+    log::set_max_level(LevelFilter::Debug);
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(record) = rx.recv().await {
+            let _ = crate::push_log_entry(&app_handle, record.level, "CLI", record.message, record.module);
+        }
+    });
+    Ok(())
+}
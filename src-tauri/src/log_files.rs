@@ -0,0 +1,193 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{NaiveDate, Utc};
+
+use crate::LogEntry;
+
+// A file is closed and a new one started past this size or when the day changes
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+// Older files are deleted on rotation
+const MAX_AGE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+const MAX_FILES: usize = 30;
+
+const FILE_PREFIX: &str = "console-";
+const FILE_SUFFIX: &str = ".jsonl";
+
+struct OpenLog {
+    file: File,
+    written: u64,
+    day: NaiveDate,
+}
+
+// Persists console entries as JSON lines under <install>/logs/
+#[derive(Default)]
+pub(crate) struct LogFileSink {
+    dir: Option<PathBuf>,
+    current: Option<OpenLog>,
+    // Set by the first failed open or write: logged once, then nothing is tried until the directory changes
+    failed: bool,
+}
+
+impl LogFileSink {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    pub(crate) fn set_install_dir(&mut self, install_dir: &Path) {
+        let dir = install_dir.join("logs");
+        if self.dir.as_deref() != Some(dir.as_path()) {
+            self.dir = Some(dir);
+            self.current = None;
+            self.failed = false;
+        }
+    }
+
+    // Best effort: a failing disk must never break logging to the console itself
+    pub(crate) fn write(&mut self, entry: &LogEntry) {
+        if self.failed {
+            return;
+        }
+        let Ok(mut line) = serde_json::to_vec(entry) else { return };
+        line.push(b'\n');
+
+        let today = Utc::now().date_naive();
+        let needs_rotation = match &self.current {
+            Some(open) => open.day != today || open.written + line.len() as u64 > MAX_FILE_BYTES,
+            None => true,
+        };
+        if needs_rotation {
+            self.current = None;
+            if let Err(e) = self.rotate(today) {
+                self.fail(&format!("Failed to open console log file: {}", e));
+                return;
+            }
+        }
+
+        if let Some(open) = self.current.as_mut() {
+            match open.file.write_all(&line) {
+                Ok(_) => open.written += line.len() as u64,
+                Err(e) => self.fail(&format!("Failed to write console log file: {}", e)),
+            }
+        }
+    }
+
+    fn fail(&mut self, message: &str) {
+        self.current = None;
+        self.failed = true;
+        let dir = self.dir.as_deref().map(|d| d.display().to_string()).unwrap_or_default();
+        log::warn!("{} (logging to {} stops until the install directory changes)", message, dir);
+    }
+
+    fn rotate(&mut self, today: NaiveDate) -> std::io::Result<()> {
+        let Some(dir) = self.dir.clone() else { return Ok(()) };
+        fs::create_dir_all(&dir)?;
+        prune(&dir);
+
+        let name = format!("{}{}{}", FILE_PREFIX, Utc::now().format("%Y%m%d-%H%M%S%.3f"), FILE_SUFFIX);
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(name))?;
+        self.current = Some(OpenLog { file, written: 0, day: today });
+        Ok(())
+    }
+}
+
+// Log files in the directory, oldest first (names sort chronologically)
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.starts_with(FILE_PREFIX) && n.ends_with(FILE_SUFFIX))
+                    .unwrap_or(false)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
+fn prune(dir: &Path) {
+    let files = log_files(dir);
+    let now = SystemTime::now();
+    // Leave room for the file about to be created
+    let excess = (files.len() + 1).saturating_sub(MAX_FILES);
+    for (i, path) in files.iter().enumerate() {
+        let too_old = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map(|age| age > MAX_AGE)
+            .unwrap_or(false);
+        if i < excess || too_old {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Most recent `limit` entries across all log files, in chronological order
+pub(crate) fn read_history(dir: &Path, limit: usize) -> Vec<LogEntry> {
+    let mut collected: Vec<LogEntry> = Vec::new();
+    for path in log_files(dir).iter().rev() {
+        let Ok(file) = File::open(path) else { continue };
+        let mut entries: Vec<LogEntry> = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        let take = limit - collected.len();
+        if entries.len() > take {
+            entries.drain(..entries.len() - take);
+        }
+        entries.append(&mut collected);
+        collected = entries;
+        if collected.len() >= limit {
+            break;
+        }
+    }
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console_settings::LogLevel;
+
+    fn entry(message: &str) -> LogEntry {
+        LogEntry { timestamp: Utc::now(), level: LogLevel::Info, source: "GUI".to_string(), message: message.to_string(), module: None }
+    }
+
+    #[test]
+    fn a_failing_directory_is_given_up_until_it_changes() {
+        let base = std::env::temp_dir().join(format!("ps-log-files-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let broken = base.join("broken");
+        fs::create_dir_all(&broken).unwrap();
+        // "logs" is a file here, so the log directory cannot be created
+        fs::write(broken.join("logs"), b"").unwrap();
+
+        let mut sink = LogFileSink::new();
+        sink.set_install_dir(&broken);
+        sink.write(&entry("first"));
+        assert!(sink.failed);
+        sink.write(&entry("second"));
+        assert!(sink.failed && sink.current.is_none());
+
+        let working = base.join("working");
+        sink.set_install_dir(&working);
+        sink.write(&entry("third"));
+        assert!(!sink.failed);
+        let history = read_history(&working.join("logs"), 10);
+        assert_eq!(history.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), ["third"]);
+        let _ = fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+const MAX_NAME_LEN: usize = 100;
+// Device names Windows refuses as file names, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "lpt1", "lpt2",
+    "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PathError {
+    InvalidName { name: String, reason: &'static str },
+    InvalidInstallPath { path: String, reason: &'static str },
+    Traversal { path: String },
+    Absolute { path: String },
+    Escape { path: String },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::InvalidName { name, reason } => write!(f, "Invalid name '{}': {}", name, reason),
+            PathError::InvalidInstallPath { path, reason } => write!(f, "Invalid install path '{}': {}", path, reason),
+            PathError::Traversal { path } => write!(f, "Path '{}' must not contain '..' or '.' segments", path),
+            PathError::Absolute { path } => write!(f, "Path '{}' must be relative to the install directory", path),
+            PathError::Escape { path } => write!(f, "Path '{}' resolves outside the install directory", path),
+        }
+    }
+}
+
+// Commands report errors as strings; this keeps `?` working in them
+impl From<PathError> for String {
+    fn from(e: PathError) -> Self {
+        e.to_string()
+    }
+}
+
+// One path segment that is safe to create on every platform
+fn check_component(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("must not be empty");
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err("is too long");
+    }
+    if name == "." || name == ".." {
+        return Err("must not be '.' or '..'");
+    }
+    if name.starts_with('.') {
+        return Err("must not start with '.'");
+    }
+    if name.ends_with('.') || name.ends_with(' ') || name.starts_with(' ') {
+        return Err("must not start with a space or end with a space or '.'");
+    }
+    if name.chars().any(|c| c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')) {
+        return Err("must not contain path separators or any of : * ? \" < > |");
+    }
+    let stem = name.split('.').next().unwrap_or(name).to_ascii_lowercase();
+    if RESERVED_NAMES.contains(&stem.as_str()) {
+        return Err("is a reserved device name on Windows");
+    }
+    Ok(())
+}
+
+// A repository folder name: exactly one path segment, never "..", a separator or a drive
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct RepoName(String);
+
+impl RepoName {
+    pub(crate) fn parse(name: &str) -> Result<Self, PathError> {
+        check_component(name).map_err(|reason| PathError::InvalidName { name: name.to_string(), reason })?;
+        Ok(Self(name.to_string()))
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RepoName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Plain file name (e.g. a start script) with the same rules as a repo name
+pub(crate) fn file_name(name: &str) -> Result<&str, PathError> {
+    check_component(name).map_err(|reason| PathError::InvalidName { name: name.to_string(), reason })?;
+    Ok(name)
+}
+
+// Resolves caller-supplied relative paths so they stay inside the installation
+#[derive(Debug, Clone)]
+pub(crate) struct InstallRoot {
+    root: PathBuf,
+    // Symlinks resolved; compared against so a link inside the install cannot lead out of it
+    canonical: PathBuf,
+}
+
+impl InstallRoot {
+    // The directory does not have to exist yet; then nothing inside it can be a symlink either
+    pub(crate) fn new(install_path: &str) -> Result<Self, PathError> {
+        let root = PathBuf::from(install_path);
+        if install_path.trim().is_empty() {
+            return Err(PathError::InvalidInstallPath { path: install_path.to_string(), reason: "is empty" });
+        }
+        if !root.is_absolute() {
+            return Err(PathError::InvalidInstallPath { path: install_path.to_string(), reason: "must be absolute" });
+        }
+        if root.components().any(|c| matches!(c, Component::ParentDir | Component::CurDir)) {
+            return Err(PathError::InvalidInstallPath { path: install_path.to_string(), reason: "must not contain '..' or '.' segments" });
+        }
+        let canonical = canonicalize_existing(&root).unwrap_or_else(|| root.clone());
+        Ok(Self { root, canonical })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.root
+    }
+
+    // `relative` may use either separator; only plain segments are accepted
+    pub(crate) fn resolve(&self, relative: &str) -> Result<PathBuf, PathError> {
+        let normalized = relative.replace('\\', "/");
+        if normalized.starts_with('/') || Path::new(relative).is_absolute() || Path::new(&normalized).has_root() {
+            return Err(PathError::Absolute { path: relative.to_string() });
+        }
+        let mut resolved = self.root.clone();
+        for segment in normalized.split('/').filter(|s| !s.is_empty()) {
+            // A ':' is a drive ("C:x") or an alternate data stream on Windows
+            if segment.contains(':') {
+                return Err(PathError::Absolute { path: relative.to_string() });
+            }
+            match Path::new(segment).components().next() {
+                Some(Component::Normal(_)) if segment != "." && segment != ".." => resolved.push(segment),
+                Some(Component::Prefix(_)) | Some(Component::RootDir) => return Err(PathError::Absolute { path: relative.to_string() }),
+                _ => return Err(PathError::Traversal { path: relative.to_string() }),
+            }
+        }
+        self.check_contained(&resolved, relative)?;
+        Ok(resolved)
+    }
+
+    pub(crate) fn repo_dir(&self, repo: &RepoName) -> Result<PathBuf, PathError> {
+        self.resolve(&format!("repos/{}", repo))
+    }
+
+    pub(crate) fn env_dir(&self, repo: &RepoName) -> Result<PathBuf, PathError> {
+        self.resolve(&format!("envs/{}", repo))
+    }
+
+    // Built the same way as `canonical`, so a root that does not exist yet still contains its children
+    fn check_contained(&self, path: &Path, original: &str) -> Result<(), PathError> {
+        match canonicalize_existing(path) {
+            Some(real) if real.starts_with(&self.canonical) => Ok(()),
+            // A dangling symlink cannot be canonicalized; treat it as leaving the install
+            _ => Err(PathError::Escape { path: original.to_string() }),
+        }
+    }
+}
+
+// Canonicalizes the deepest existing ancestor (that is where a symlink would redirect the rest) and
+// appends the part that does not exist yet. Also gives both sides the same \\?\ prefix on Windows.
+fn canonicalize_existing(path: &Path) -> Option<PathBuf> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    while std::fs::symlink_metadata(existing).is_err() {
+        missing.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
+    let mut real = std::fs::canonicalize(existing).ok()?;
+    real.extend(missing.iter().rev());
+    Some(real)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh directory under the system temp dir, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("ps-paths-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn root_at(path: &Path) -> InstallRoot {
+        InstallRoot::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn repo_name_accepts_plain_names() {
+        assert_eq!(RepoName::parse("ComfyUI").unwrap().as_str(), "ComfyUI");
+        assert!(RepoName::parse("stable-diffusion_webui.v2").is_ok());
+    }
+
+    #[test]
+    fn repo_name_rejects_unsafe_names() {
+        for name in ["", ".", "..", ".git", "a/b", "a\\b", "C:", "name.", " name", "con", "NUL.txt", "a*b", "x".repeat(101).as_str()] {
+            assert!(RepoName::parse(name).is_err(), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn resolves_inside_a_root_that_does_not_exist_yet() {
+        let tmp = TempDir::new("missing");
+        let root = root_at(&tmp.0.join("missing").join("deeper"));
+        assert_eq!(root.resolve("repos/foo").unwrap(), tmp.0.join("missing").join("deeper").join("repos").join("foo"));
+        assert!(root.repo_dir(&RepoName::parse("foo").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn resolves_inside_an_existing_root() {
+        let tmp = TempDir::new("existing");
+        std::fs::create_dir_all(tmp.0.join("repos").join("foo")).unwrap();
+        let root = root_at(&tmp.0);
+        assert_eq!(root.resolve("repos\\foo").unwrap(), tmp.0.join("repos").join("foo"));
+        assert_eq!(root.resolve("repos/bar/new").unwrap(), tmp.0.join("repos").join("bar").join("new"));
+    }
+
+    #[test]
+    fn rejects_parent_and_current_dir_segments() {
+        let tmp = TempDir::new("traversal");
+        let root = root_at(&tmp.0);
+        for relative in ["..", "../x", "repos/../../x", "repos/./foo", "repos\\..\\.."] {
+            assert!(matches!(root.resolve(relative), Err(PathError::Traversal { .. })), "{:?}", relative);
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_input() {
+        let tmp = TempDir::new("absolute");
+        let root = root_at(&tmp.0);
+        for relative in ["/etc/passwd", "\\server\\share", "C:\\Windows", "C:x", "repos/file:stream"] {
+            assert!(matches!(root.resolve(relative), Err(PathError::Absolute { .. })), "{:?}", relative);
+        }
+        let inside = tmp.0.join("repos");
+        assert!(root.resolve(inside.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn rejects_relative_or_dotted_install_paths() {
+        assert!(InstallRoot::new("").is_err());
+        assert!(InstallRoot::new("relative/path").is_err());
+        let tmp = TempDir::new("dotted");
+        assert!(InstallRoot::new(tmp.0.join("..").join("x").to_str().unwrap()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_that_lead_out_of_the_root() {
+        let tmp = TempDir::new("symlink");
+        let root_dir = tmp.0.join("root");
+        let outside = tmp.0.join("outside");
+        std::fs::create_dir_all(root_dir.join("repos")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root_dir.join("repos").join("escape")).unwrap();
+        std::os::unix::fs::symlink(tmp.0.join("nowhere"), root_dir.join("repos").join("dangling")).unwrap();
+        std::os::unix::fs::symlink(root_dir.join("repos"), root_dir.join("inner")).unwrap();
+
+        let root = root_at(&root_dir);
+        assert!(matches!(root.resolve("repos/escape/x"), Err(PathError::Escape { .. })));
+        assert!(matches!(root.resolve("repos/dangling"), Err(PathError::Escape { .. })));
+        assert!(root.resolve("inner/foo").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn root_reached_through_a_symlink_contains_its_children() {
+        let tmp = TempDir::new("linked-root");
+        std::fs::create_dir_all(tmp.0.join("real")).unwrap();
+        std::os::unix::fs::symlink(tmp.0.join("real"), tmp.0.join("link")).unwrap();
+        let root = root_at(&tmp.0.join("link").join("not-yet"));
+        assert!(root.resolve("repos/foo").is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::Emitter;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+// Lines waiting to be batched; when full, readers stop draining the pipe and the child blocks on write
+const CHANNEL_CAPACITY: usize = 1024;
+// At most one output event per interval per stream id
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_BATCH_LINES: usize = 500;
+const MAX_BATCH_BYTES: usize = 256 * 1024;
+// pip progress bars can produce megabyte-long "lines" of \r updates
+pub(crate) const MAX_LINE_BYTES: usize = 16 * 1024;
+// How long to wait for the pipes to close once the command itself exited; a process that left
+// the group keeps them open, and the job then finishes without its remaining output
+pub(crate) const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct BatchLine {
+    pub(crate) stream: &'static str, // "stdout" or "stderr"
+    pub(crate) data: String,
+}
+
+// Payload of command-output-<id>: every line produced since the previous event, in order
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct OutputBatch {
+    pub(crate) lines: Vec<BatchLine>,
+}
+
+// Reads both pipes and emits their lines as batches on `event`; the handle resolves after the final flush
+pub(crate) fn spawn_output_pump<O, E>(app_handle: tauri::AppHandle, event: String, stdout: O, stderr: E) -> JoinHandle<()>
+where
+    O: AsyncRead + Unpin + Send + 'static,
+    E: AsyncRead + Unpin + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(read_lines(stdout, "stdout", tx.clone()));
+    tokio::spawn(read_lines(stderr, "stderr", tx));
+    tokio::spawn(batch_and_emit(app_handle, event, rx))
+}
+
+async fn read_lines<R: AsyncRead + Unpin>(reader: R, stream: &'static str, tx: mpsc::Sender<BatchLine>) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if tx.send(BatchLine { stream, data: line_text(&buf) }).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Keeps only the last carriage-return segment, like a terminal would show it, cut to MAX_LINE_BYTES
+fn line_text(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let text = text.trim_end_matches(['\r', '\n']);
+    let mut data = text.rsplit('\r').next().unwrap_or_default().to_string();
+    if data.len() > MAX_LINE_BYTES {
+        let mut cut = MAX_LINE_BYTES;
+        while !data.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        data.truncate(cut);
+    }
+    data
+}
+
+// Lines collected between two output events
+#[derive(Default)]
+struct Batcher {
+    lines: Vec<BatchLine>,
+    bytes: usize,
+}
+
+impl Batcher {
+    // A full batch takes no more lines until it is flushed
+    fn is_full(&self) -> bool {
+        self.lines.len() >= MAX_BATCH_LINES || self.bytes >= MAX_BATCH_BYTES
+    }
+
+    fn push(&mut self, line: BatchLine) {
+        self.bytes += line.data.len();
+        self.lines.push(line);
+    }
+
+    fn take(&mut self) -> Option<OutputBatch> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        self.bytes = 0;
+        Some(OutputBatch { lines: std::mem::take(&mut self.lines) })
+    }
+}
+
+async fn batch_and_emit(app_handle: tauri::AppHandle, event: String, rx: mpsc::Receiver<BatchLine>) {
+    batch_lines(rx, |batch| {
+        let _ = app_handle.emit(&event, batch);
+    })
+    .await;
+}
+
+// Hands lines to `emit` at most once per FLUSH_INTERVAL, plus once more after both pipes closed
+async fn batch_lines(mut rx: mpsc::Receiver<BatchLine>, mut emit: impl FnMut(OutputBatch)) {
+    let mut batcher = Batcher::default();
+    let mut ticker = tokio::time::interval_at(Instant::now() + FLUSH_INTERVAL, FLUSH_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            // A full batch stops receiving until the next tick, which is what throttles a chatty child
+            line = rx.recv(), if !batcher.is_full() => match line {
+                Some(line) => batcher.push(line),
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if let Some(batch) = batcher.take() {
+                    emit(batch);
+                }
+            }
+        }
+    }
+    if let Some(batch) = batcher.take() {
+        emit(batch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(data: String) -> BatchLine {
+        BatchLine { stream: "stdout", data }
+    }
+
+    // Runs batch_lines on `rx` and records each batch's size with the time it was emitted
+    fn record(rx: mpsc::Receiver<BatchLine>) -> (JoinHandle<()>, mpsc::UnboundedReceiver<(usize, Instant)>) {
+        let (emitted_tx, emitted) = mpsc::unbounded_channel();
+        let task = tokio::spawn(batch_lines(rx, move |batch| {
+            let _ = emitted_tx.send((batch.lines.len(), Instant::now()));
+        }));
+        (task, emitted)
+    }
+
+    #[test]
+    fn batches_fill_up_at_the_line_or_byte_limit() {
+        let mut batcher = Batcher::default();
+        for i in 0..MAX_BATCH_LINES - 1 {
+            batcher.push(line(i.to_string()));
+        }
+        assert!(!batcher.is_full());
+        batcher.push(line("last".to_string()));
+        assert!(batcher.is_full());
+        assert_eq!(batcher.take().unwrap().lines.len(), MAX_BATCH_LINES);
+        assert!(!batcher.is_full());
+        assert!(batcher.take().is_none());
+
+        let long = "x".repeat(MAX_LINE_BYTES);
+        for _ in 0..MAX_BATCH_BYTES / MAX_LINE_BYTES - 1 {
+            batcher.push(line(long.clone()));
+        }
+        assert!(!batcher.is_full());
+        batcher.push(line(long));
+        assert!(batcher.is_full());
+        assert_eq!(batcher.take().unwrap().lines.len(), MAX_BATCH_BYTES / MAX_LINE_BYTES);
+        assert!(!batcher.is_full());
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let raw = "a".repeat(MAX_LINE_BYTES + 10) + "\r\n";
+        assert_eq!(line_text(raw.as_bytes()), "a".repeat(MAX_LINE_BYTES));
+        // Never in the middle of a character
+        let raw = "a".to_string() + &"é".repeat(MAX_LINE_BYTES);
+        let text = line_text(raw.as_bytes());
+        assert_eq!(text.len(), MAX_LINE_BYTES - 1);
+        assert!(text.ends_with('é'));
+        assert_eq!(line_text(b"10%\r50%\r100%\r\n"), "100%");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_quiet_stream_is_flushed_by_the_timer() {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let start = Instant::now();
+        let (task, mut emitted) = record(rx);
+        tx.send(line("one".to_string())).await.unwrap();
+
+        // The pipe is still open; only the timer can flush
+        let (count, at) = emitted.recv().await.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(at - start, FLUSH_INTERVAL);
+
+        tx.send(line("two".to_string())).await.unwrap();
+        let (count, next) = emitted.recv().await.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(next - at, FLUSH_INTERVAL);
+
+        drop(tx);
+        task.await.unwrap();
+        assert!(emitted.recv().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_full_batch_waits_for_the_next_tick() {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        for i in 0..MAX_BATCH_LINES + 10 {
+            tx.send(line(i.to_string())).await.unwrap();
+        }
+        drop(tx);
+        let start = Instant::now();
+        let (task, mut emitted) = record(rx);
+        task.await.unwrap();
+
+        let (first, at) = emitted.recv().await.unwrap();
+        assert_eq!(first, MAX_BATCH_LINES);
+        assert_eq!(at - start, FLUSH_INTERVAL);
+        // The rest goes out in the final flush once the pipes closed
+        assert_eq!(emitted.recv().await.unwrap().0, 10);
+        assert!(emitted.recv().await.is_none());
+    }
+}
@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+// Hosts the webview may reach through proxy_request
+pub(crate) const CATALOG_HOSTS: &[&str] = &["server.portables.dev"];
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 5;
+pub(crate) const MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum ProxyError {
+    InvalidUrl { message: String },
+    SchemeRejected { scheme: String },
+    HostRejected { host: String },
+    Timeout { url: String },
+    BadStatus { status: u16, url: String },
+    TooLarge { limit: usize },
+    InvalidResponse { message: String },
+    Network { message: String },
+}
+
+impl std::fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyError::InvalidUrl { message } => write!(f, "Invalid URL: {}", message),
+            ProxyError::SchemeRejected { scheme } => write!(f, "Scheme '{}' is not allowed", scheme),
+            ProxyError::HostRejected { host } => write!(f, "Host '{}' is not an allowed catalog host", host),
+            ProxyError::Timeout { url } => write!(f, "Request to {} timed out", url),
+            ProxyError::BadStatus { status, url } => write!(f, "Request to {} failed with status: {}", url, status),
+            ProxyError::TooLarge { limit } => write!(f, "Response exceeds {} bytes", limit),
+            ProxyError::InvalidResponse { message } => write!(f, "Invalid response: {}", message),
+            ProxyError::Network { message } => write!(f, "Network error: {}", message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProxyError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ProxyError::Timeout { url: e.url().map(|u| u.to_string()).unwrap_or_default() }
+        } else if let Some(status) = e.status() {
+            ProxyError::BadStatus { status: status.as_u16(), url: e.url().map(|u| u.to_string()).unwrap_or_default() }
+        } else {
+            ProxyError::Network { message: e.to_string() }
+        }
+    }
+}
+
+pub(crate) fn is_allowed_host(host: &str, allowed: &[String]) -> bool {
+    allowed.iter().any(|h| h.eq_ignore_ascii_case(host))
+}
+
+// Parses the URL and checks it against the allowlist before any request goes out
+pub(crate) fn check_url(url: &str, allowed: &[String]) -> Result<reqwest::Url, ProxyError> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| ProxyError::InvalidUrl { message: e.to_string() })?;
+    if parsed.scheme() != "https" {
+        return Err(ProxyError::SchemeRejected { scheme: parsed.scheme().to_string() });
+    }
+    let host = parsed.host_str().unwrap_or_default().to_string();
+    if !is_allowed_host(&host, allowed) {
+        return Err(ProxyError::HostRejected { host });
+    }
+    Ok(parsed)
+}
+
+// A redirect is followed only to a URL check_url would accept
+fn redirect_allowed(url: &reqwest::Url, allowed: &[String]) -> bool {
+    url.scheme() == "https" && url.host_str().map(|h| is_allowed_host(h, allowed)).unwrap_or(false)
+}
+
+// Client with timeouts and a redirect policy that never leaves the allowlist
+pub(crate) fn build_client(allowed: Vec<String>) -> Result<reqwest::Client, ProxyError> {
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS || !redirect_allowed(attempt.url(), &allowed) {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    });
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .redirect(policy)
+        .user_agent(concat!("PortableSource-App/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| ProxyError::Network { message: e.to_string() })
+}
+
+// Reads the body chunk by chunk so an oversized response is cut off instead of buffered
+pub(crate) async fn read_limited(mut response: reqwest::Response, limit: usize) -> Result<Vec<u8>, ProxyError> {
+    if response.content_length().map(|len| len as usize > limit).unwrap_or(false) {
+        return Err(ProxyError::TooLarge { limit });
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(ProxyError::TooLarge { limit });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+pub(crate) async fn fetch_text(url: &str, allowed: &[String]) -> Result<String, ProxyError> {
+    let parsed = check_url(url, allowed)?;
+    let client = build_client(allowed.to_vec())?;
+    let response = client.get(parsed.clone()).send().await?;
+    if !response.status().is_success() {
+        return Err(ProxyError::BadStatus { status: response.status().as_u16(), url: parsed.to_string() });
+    }
+    let body = read_limited(response, MAX_RESPONSE_BYTES).await?;
+    Ok(String::from_utf8_lossy(&body).to_string())
+}
+
+pub(crate) fn default_allowed_hosts() -> Vec<String> {
+    CATALOG_HOSTS.iter().map(|h| h.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, MockServer};
+
+    fn local() -> Vec<String> {
+        vec!["127.0.0.1".to_string()]
+    }
+
+    #[tokio::test]
+    async fn hosts_outside_the_allowlist_are_rejected() {
+        let allowed = default_allowed_hosts();
+        assert!(check_url("https://SERVER.portables.dev/api/repositories/top", &allowed).is_ok());
+        assert!(matches!(check_url("https://evil.example/api", &allowed), Err(ProxyError::HostRejected { host }) if host == "evil.example"));
+        assert!(matches!(check_url("https://server.portables.dev.evil.example/", &allowed), Err(ProxyError::HostRejected { .. })));
+        assert!(matches!(fetch_text("https://evil.example/api", &allowed).await, Err(ProxyError::HostRejected { .. })));
+        assert!(matches!(check_url("not a url", &allowed), Err(ProxyError::InvalidUrl { .. })));
+    }
+
+    #[tokio::test]
+    async fn plain_http_is_rejected_before_any_request() {
+        assert!(matches!(check_url("http://server.portables.dev/", &default_allowed_hosts()), Err(ProxyError::SchemeRejected { scheme }) if scheme == "http"));
+
+        let server = MockServer::start(vec![response("200 OK", &[], "hello")]).await;
+        let result = fetch_text(&format!("{}/", server.url), &local()).await;
+        assert!(matches!(result, Err(ProxyError::SchemeRejected { .. })));
+        assert_eq!(server.request_count(), 0);
+    }
+
+    #[test]
+    fn redirects_stay_on_https_and_the_allowlist() {
+        let allowed = default_allowed_hosts();
+        let url = |raw: &str| reqwest::Url::parse(raw).unwrap();
+        assert!(redirect_allowed(&url("https://server.portables.dev/next"), &allowed));
+        assert!(!redirect_allowed(&url("https://evil.example/next"), &allowed));
+        assert!(!redirect_allowed(&url("http://server.portables.dev/next"), &allowed));
+    }
+
+    #[tokio::test]
+    async fn redirects_to_other_hosts_are_not_followed() {
+        let target = MockServer::start(vec![response("200 OK", &[], "secret")]).await;
+        let location = target.url.replace("127.0.0.1", "localhost") + "/elsewhere";
+        let server = MockServer::start(vec![response("302 Found", &[("Location", &location)], "")]).await;
+
+        let client = build_client(local()).unwrap();
+        let response = client.get(format!("{}/start", server.url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(target.request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn bodies_over_the_limit_are_cut_off() {
+        let body = "x".repeat(64);
+        let server = MockServer::start(vec![
+            response("200 OK", &[], &body),
+            response("200 OK", &[], &body),
+            // No Content-Length: the limit has to be enforced while reading
+            format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{}", body),
+        ])
+        .await;
+        let client = build_client(local()).unwrap();
+        let get = || client.get(format!("{}/", server.url)).send();
+
+        assert_eq!(read_limited(get().await.unwrap(), 64).await.unwrap().len(), 64);
+        assert!(matches!(read_limited(get().await.unwrap(), 63).await, Err(ProxyError::TooLarge { limit: 63 })));
+        assert!(matches!(read_limited(get().await.unwrap(), 16).await, Err(ProxyError::TooLarge { limit: 16 })));
+    }
+}
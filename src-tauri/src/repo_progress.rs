@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::Emitter;
+
+use crate::launcher;
+
+// The installer has no progress callback of its own, so phases are inferred by watching the disk.
+// Only phases that leave a trace there are reported; the log text is too loose to go by.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Ordered: a tracker never moves back to an earlier phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RepoPhase {
+    Clone,
+    Venv,
+    Packages,
+    Launcher,
+}
+
+// Where a progress event came from; the UI should treat inferred phases as a hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ProgressSource {
+    Inferred,
+}
+
+// Payload of repo-install-progress-<id>; done/total count the repo's requirements, total is 0 when unknown
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RepoProgressEvent {
+    pub(crate) repo: String,
+    pub(crate) phase: RepoPhase,
+    pub(crate) done: usize,
+    pub(crate) total: usize,
+    pub(crate) source: ProgressSource,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    phase: Option<RepoPhase>,
+    done: usize,
+    total: usize,
+    baseline_packages: Option<usize>,
+    // Normalized names from the repo's requirements.txt, read once the checkout has it
+    requirements: Option<Vec<String>>,
+}
+
+struct Tracker {
+    app_handle: tauri::AppHandle,
+    event: String,
+    repo: String,
+    state: Mutex<TrackerState>,
+}
+
+impl Tracker {
+    // Emits only on change
+    fn report(&self, phase: RepoPhase, done: Option<usize>) {
+        let event = {
+            let Ok(mut state) = self.state.lock() else { return };
+            if state.phase.map(|current| phase < current).unwrap_or(false) {
+                return;
+            }
+            let done = done.unwrap_or(if state.phase == Some(phase) { state.done } else { 0 });
+            if state.phase == Some(phase) && state.done == done {
+                return;
+            }
+            state.phase = Some(phase);
+            state.done = done;
+            RepoProgressEvent { repo: self.repo.clone(), phase, done, total: state.total, source: ProgressSource::Inferred }
+        };
+        let _ = self.app_handle.emit(&self.event, event);
+    }
+}
+
+// Stops the disk poller when the install finishes
+pub(crate) struct ProgressGuard {
+    poller: JoinHandle<()>,
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        self.poller.abort();
+    }
+}
+
+// Starts reporting phases of installing/updating `input` (a name or URL) as `event`
+pub(crate) fn track(app_handle: &tauri::AppHandle, event: String, install_dir: &Path, input: &str, updating: bool) -> ProgressGuard {
+    let repo = repo_dir_name(input);
+    let tracker = Tracker { app_handle: app_handle.clone(), event, repo: repo.clone(), state: Mutex::new(TrackerState::default()) };
+    // Both flows start with the checkout: a clone, or a pull when updating
+    tracker.report(RepoPhase::Clone, Some(0));
+
+    let install_dir = install_dir.to_path_buf();
+    let poller = tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            poll_disk(&tracker, &install_dir, &repo, updating);
+        }
+    });
+    ProgressGuard { poller }
+}
+
+fn poll_disk(tracker: &Tracker, install_dir: &Path, repo: &str, updating: bool) {
+    let env_dir = install_dir.join("envs").join(repo);
+    let installed_names = site_packages(&env_dir).map(|dir| installed_packages(&dir));
+    let requirements = tracker_requirements(tracker, &install_dir.join("repos").join(repo));
+
+    // With a requirements file, done counts its entries that are installed; otherwise newly installed packages
+    let (installed, progressed) = {
+        let Ok(mut state) = tracker.state.lock() else { return };
+        let count = installed_names.as_ref().map(|names| names.len()).unwrap_or(0);
+        let baseline = match installed_names {
+            Some(_) => *state.baseline_packages.get_or_insert(count),
+            None => 0,
+        };
+        let installed = match (&requirements, &installed_names) {
+            (Some(required), Some(names)) => required.iter().filter(|name| names.contains(*name)).count(),
+            _ => count.saturating_sub(baseline),
+        };
+        (installed, count > baseline)
+    };
+
+    // Everything already exists during an update; only new packages tell us something
+    if updating {
+        if progressed {
+            tracker.report(RepoPhase::Packages, Some(installed));
+        }
+        return;
+    }
+
+    let script = install_dir.join("repos").join(repo).join(launcher::launcher_file_name(repo));
+    if script.is_file() {
+        tracker.report(RepoPhase::Launcher, None);
+    } else if progressed {
+        tracker.report(RepoPhase::Packages, Some(installed));
+    } else if env_dir.is_dir() {
+        tracker.report(RepoPhase::Venv, None);
+    }
+}
+
+// Reads requirements.txt the first time the checkout has one and fixes the event total to its size
+fn tracker_requirements(tracker: &Tracker, repo_dir: &Path) -> Option<Vec<String>> {
+    let Ok(mut state) = tracker.state.lock() else { return None };
+    if state.requirements.is_none() {
+        let content = std::fs::read_to_string(repo_dir.join("requirements.txt")).ok()?;
+        let names = requirement_names(&content);
+        state.total = names.len();
+        state.requirements = Some(names);
+    }
+    state.requirements.clone()
+}
+
+// pip treats "-", "_" and "." in project names alike and ignores case
+fn normalize_package(name: &str) -> String {
+    name.trim().to_lowercase().replace(['-', '.'], "_")
+}
+
+// Project names listed in a requirements file; options, includes and URLs without #egg= are skipped
+fn requirement_names(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.split(" #").next().unwrap_or("").trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('-') {
+            continue;
+        }
+        let name = if line.contains("://") {
+            match line.split("#egg=").nth(1) {
+                Some(egg) => egg.split(['&', ' ']).next().unwrap_or(""),
+                None => continue,
+            }
+        } else {
+            let end = line.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))).unwrap_or(line.len());
+            &line[..end]
+        };
+        let name = normalize_package(name);
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// Folder the installer creates for a name, owner/name or clone URL
+pub(crate) fn repo_dir_name(input: &str) -> String {
+    let trimmed = input.trim().trim_end_matches('/');
+    let last = trimmed.rsplit(['/', ':']).next().unwrap_or(trimmed);
+    last.strip_suffix(".git").unwrap_or(last).to_string()
+}
+
+fn site_packages(env_dir: &Path) -> Option<PathBuf> {
+    let windows = env_dir.join("Lib").join("site-packages");
+    if windows.is_dir() {
+        return Some(windows);
+    }
+    std::fs::read_dir(env_dir.join("lib"))
+        .ok()?
+        .flatten()
+        .map(|e| e.path().join("site-packages"))
+        .find(|p| p.is_dir())
+}
+
+// Normalized project names of the *.dist-info folders, e.g. "typing_extensions" for typing_extensions-4.8.0.dist-info
+fn installed_packages(site_packages: &Path) -> HashSet<String> {
+    std::fs::read_dir(site_packages)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| {
+                    let file_name = e.file_name();
+                    let stem = file_name.to_str()?.strip_suffix(".dist-info")?.to_string();
+                    let name = stem.rsplit_once('-').map(|(name, _)| name).unwrap_or(&stem);
+                    Some(normalize_package(name))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repo_dir_name_handles_names_and_urls() {
+        assert_eq!(repo_dir_name("ComfyUI"), "ComfyUI");
+        assert_eq!(repo_dir_name("comfyanonymous/ComfyUI"), "ComfyUI");
+        assert_eq!(repo_dir_name("https://github.com/comfyanonymous/ComfyUI.git"), "ComfyUI");
+        assert_eq!(repo_dir_name("https://github.com/comfyanonymous/ComfyUI/"), "ComfyUI");
+        assert_eq!(repo_dir_name("git@github.com:comfyanonymous/ComfyUI.git"), "ComfyUI");
+    }
+
+    #[test]
+    fn requirement_names_skip_options_and_comments() {
+        let content = "\
+# core
+torch>=2.1 ; sys_platform != 'darwin'
+Pillow==10.0.1  # images
+typing-extensions
+-r extra.txt
+--extra-index-url https://download.pytorch.org/whl/cu121
+opencv_python.headless[contrib]~=4.8
+git+https://github.com/openai/CLIP.git#egg=clip
+https://example.com/wheel.whl
+pillow
+";
+        assert_eq!(requirement_names(content), vec!["torch", "pillow", "typing_extensions", "opencv_python_headless", "clip"]);
+    }
+
+    #[test]
+    fn requirements_match_dist_info_names() {
+        let dir = std::env::temp_dir().join(format!("ps-repo-progress-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for name in ["Pillow-10.0.1.dist-info", "typing_extensions-4.8.0.dist-info", "torch-2.1.0+cu121.dist-info", "pip"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        let installed = installed_packages(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let required = requirement_names("torch\npillow\ntyping-extensions\nnumpy\n");
+        assert_eq!(required.iter().filter(|name| installed.contains(*name)).count(), 3);
+        assert!(!installed.contains("pip"));
+    }
+
+    #[test]
+    fn progress_events_say_they_are_inferred() {
+        let event = RepoProgressEvent { repo: "ComfyUI".to_string(), phase: RepoPhase::Packages, done: 3, total: 10, source: ProgressSource::Inferred };
+        let value = serde_json::to_value(event).unwrap();
+        assert_eq!(value["phase"], "packages");
+        assert_eq!(value["source"], "inferred");
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::paths::{InstallRoot, PathError, RepoName};
+
+// Antivirus scanners and indexers often hold a file for a moment after a process exits
+const RETRIES: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+// Enough to tell the user what is holding a folder without flooding the UI
+const MAX_REPORTED_FILES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RemovalStatus {
+    Removed,
+    NotFound,
+    // Some files are in use by another process; everything else was removed
+    Locked,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PathRemoval {
+    pub(crate) path: String,
+    pub(crate) status: RemovalStatus,
+    pub(crate) error: Option<String>,
+    pub(crate) locked_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RemovalReport {
+    pub(crate) success: bool,
+    pub(crate) repos: Vec<String>,
+    pub(crate) paths: Vec<PathRemoval>,
+    // Non-fatal problems, e.g. the installer could not drop a config entry
+    pub(crate) warnings: Vec<String>,
+    // Repositories a batch could not get through; the rest of the batch still ran
+    pub(crate) errors: Vec<String>,
+}
+
+impl PathRemoval {
+    // One line for a path that could not be fully deleted
+    pub(crate) fn problem(&self) -> Option<String> {
+        match self.status {
+            RemovalStatus::Removed | RemovalStatus::NotFound => None,
+            RemovalStatus::Locked => Some(format!("{}: files in use ({})", self.path, self.locked_files.join(", "))),
+            RemovalStatus::Failed => Some(format!("{}: {}", self.path, self.error.as_deref().unwrap_or("could not be deleted"))),
+        }
+    }
+}
+
+impl RemovalReport {
+    pub(crate) fn new() -> Self {
+        Self { success: true, repos: Vec::new(), paths: Vec::new(), warnings: Vec::new(), errors: Vec::new() }
+    }
+
+    pub(crate) fn fail(&mut self, error: String) {
+        self.success = false;
+        self.errors.push(error);
+    }
+
+    pub(crate) fn push(&mut self, removal: PathRemoval) {
+        if matches!(removal.status, RemovalStatus::Locked | RemovalStatus::Failed) {
+            self.success = false;
+        }
+        self.paths.push(removal);
+    }
+}
+
+// The folders that make up an installed repository; fails if either resolves outside the install
+pub(crate) fn repository_paths(root: &InstallRoot, repo: &RepoName) -> Result<Vec<std::path::PathBuf>, PathError> {
+    Ok(vec![root.env_dir(repo)?, root.repo_dir(repo)?])
+}
+
+// Names present in envs/ or repos/, including half-installed ones
+pub(crate) fn all_repository_names(install_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = ["envs", "repos"]
+        .iter()
+        .filter_map(|dir| fs::read_dir(install_dir.join(dir)).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|entry| entry.file_name().to_str().map(|n| n.to_string()))
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// Deletes a tree file by file so one locked file does not hide what else could be removed
+pub(crate) fn remove_path(path: &Path) -> PathRemoval {
+    let display = path.to_string_lossy().to_string();
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return PathRemoval { path: display, status: RemovalStatus::NotFound, error: None, locked_files: Vec::new() };
+        }
+        Err(e) => {
+            return PathRemoval { path: display, status: RemovalStatus::Failed, error: Some(e.to_string()), locked_files: Vec::new() };
+        }
+    };
+
+    let mut locked = Vec::new();
+    let mut first_error: Option<String> = None;
+    if meta.is_dir() {
+        remove_tree(path, &mut locked, &mut first_error);
+    } else if let Err(e) = remove_with_retry(path, false) {
+        record_failure(path, e, &mut locked, &mut first_error);
+    }
+
+    let status = if !locked.is_empty() {
+        RemovalStatus::Locked
+    } else if first_error.is_some() {
+        RemovalStatus::Failed
+    } else {
+        RemovalStatus::Removed
+    };
+    if locked.len() > MAX_REPORTED_FILES {
+        let extra = locked.len() - MAX_REPORTED_FILES;
+        locked.truncate(MAX_REPORTED_FILES);
+        locked.push(format!("... and {} more", extra));
+    }
+    PathRemoval { path: display, status, error: first_error, locked_files: locked }
+}
+
+// Symlinks are removed, never followed, so a link inside a repo cannot delete anything outside it
+fn remove_tree(dir: &Path, locked: &mut Vec<String>, first_error: &mut Option<String>) {
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if is_dir {
+                    remove_tree(&path, locked, first_error);
+                } else if let Err(e) = remove_with_retry(&path, false) {
+                    record_failure(&path, e, locked, first_error);
+                }
+            }
+        }
+        Err(e) => record_failure(dir, e, locked, first_error),
+    }
+    // Fails with "not empty" when something inside was locked; that file is already reported
+    if let Err(e) = remove_with_retry(dir, true) {
+        if locked.is_empty() && first_error.is_none() {
+            record_failure(dir, e, locked, first_error);
+        }
+    }
+}
+
+fn remove_with_retry(path: &Path, is_dir: bool) -> io::Result<()> {
+    let mut attempt = 0;
+    loop {
+        let result = if is_dir { fs::remove_dir(path) } else { fs::remove_file(path) };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) if attempt < RETRIES => {
+                // git marks its objects read-only, which blocks deletion on Windows
+                if e.kind() == io::ErrorKind::PermissionDenied {
+                    clear_readonly(path);
+                }
+                attempt += 1;
+                std::thread::sleep(RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn clear_readonly(path: &Path) {
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        let mut permissions = meta.permissions();
+        if permissions.readonly() {
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            let _ = fs::set_permissions(path, permissions);
+        }
+    }
+}
+
+fn record_failure(path: &Path, e: io::Error, locked: &mut Vec<String>, first_error: &mut Option<String>) {
+    if is_locked(&e) {
+        locked.push(path.to_string_lossy().to_string());
+    } else if first_error.is_none() {
+        *first_error = Some(format!("{}: {}", path.display(), e));
+    }
+}
+
+// "In use" errors: sharing/lock violations on Windows, busy files on Unix. Windows also answers
+// access denied for the executables and DLLs of a running process and for files already pending
+// deletion; read-only files were cleared before the retries, so what is still denied counts as in use.
+fn is_locked(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        #[cfg(target_os = "windows")]
+        Some(5) | Some(32) | Some(33) => true,
+        #[cfg(not(target_os = "windows"))]
+        Some(16) | Some(26) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn access_denied_counts_as_in_use() {
+        for code in [5, 32, 33] {
+            assert!(is_locked(&io::Error::from_raw_os_error(code)), "error {}", code);
+        }
+        assert!(!is_locked(&io::Error::from_raw_os_error(2)));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn busy_files_count_as_in_use() {
+        assert!(is_locked(&io::Error::from_raw_os_error(16)));
+        assert!(is_locked(&io::Error::from_raw_os_error(26)));
+        assert!(!is_locked(&io::Error::from_raw_os_error(13)));
+    }
+
+    #[test]
+    fn a_failed_repository_fails_the_report_but_keeps_the_rest() {
+        let mut report = RemovalReport::new();
+        report.repos.push("first".to_string());
+        report.fail("'second': config unavailable".to_string());
+        report.repos.push("third".to_string());
+        assert!(!report.success);
+        assert_eq!(report.repos, ["first", "third"]);
+        assert_eq!(report.errors.len(), 1);
+    }
+
+    #[test]
+    fn only_leftover_paths_are_problems() {
+        let removal = |status, error: Option<&str>, locked: &[&str]| PathRemoval {
+            path: "repos/demo".to_string(),
+            status,
+            error: error.map(str::to_string),
+            locked_files: locked.iter().map(|f| f.to_string()).collect(),
+        };
+        assert_eq!(removal(RemovalStatus::Removed, None, &[]).problem(), None);
+        assert_eq!(removal(RemovalStatus::NotFound, None, &[]).problem(), None);
+        assert_eq!(removal(RemovalStatus::Locked, None, &["a.dll", "b.exe"]).problem().unwrap(), "repos/demo: files in use (a.dll, b.exe)");
+        assert_eq!(removal(RemovalStatus::Failed, Some("denied"), &[]).problem().unwrap(), "repos/demo: denied");
+    }
+}
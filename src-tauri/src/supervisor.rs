@@ -0,0 +1,367 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::watch;
+
+use crate::jobs::{terminate_process_tree, terminate_process_trees_blocking};
+use crate::process_stream::MAX_LINE_BYTES;
+use crate::AppState;
+
+// Lines kept per repository; older output is dropped first
+const OUTPUT_BUFFER_LINES: usize = 2000;
+// How long stop/restart wait for the exit to be recorded once the tree was terminated
+const STOP_TIMEOUT: Duration = Duration::from_secs(15);
+// Shutdown cannot wait as long as a stop from the UI
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+// What to run for a repository; kept so restart_repo can relaunch it the same way
+#[derive(Debug, Clone)]
+pub(crate) struct LaunchSpec {
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) working_dir: PathBuf,
+    pub(crate) env: Vec<(String, String)>,
+}
+
+impl LaunchSpec {
+    fn display(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(|a| a.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RunningRepo {
+    pub(crate) repo: String,
+    pub(crate) pid: u32,
+    pub(crate) started_at: DateTime<Utc>,
+    pub(crate) command: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RepoOutputLine {
+    pub(crate) seq: u64,
+    pub(crate) timestamp: DateTime<Utc>,
+    pub(crate) stream: String, // "stdout" or "stderr"
+    pub(crate) data: String,
+}
+
+// Payload of the repo-exited event
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RepoExited {
+    pub(crate) repo: String,
+    pub(crate) pid: u32,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) success: bool,
+    pub(crate) stopped: bool, // true when stop_repo/restart_repo asked for it
+}
+
+struct ManagedProcess {
+    info: RunningRepo,
+    generation: u64,
+    stop_requested: bool,
+    exited: watch::Receiver<bool>,
+}
+
+#[derive(Default)]
+struct RepoSlot {
+    process: Option<ManagedProcess>,
+    last_spec: Option<LaunchSpec>,
+    next_seq: u64,
+    output: VecDeque<RepoOutputLine>,
+}
+
+pub(crate) struct Supervisor {
+    next_generation: AtomicU64,
+    repos: Mutex<HashMap<String, RepoSlot>>,
+}
+
+impl Supervisor {
+    pub(crate) fn new() -> Self {
+        Self { next_generation: AtomicU64::new(1), repos: Mutex::new(HashMap::new()) }
+    }
+
+    pub(crate) fn start(&self, app_handle: &tauri::AppHandle, repo: &str, spec: LaunchSpec) -> Result<RunningRepo, String> {
+        let mut repos = self.repos.lock().map_err(|_| "Supervisor state poisoned")?;
+        let slot = repos.entry(repo.to_string()).or_default();
+        if let Some(process) = &slot.process {
+            return Err(format!("Repository '{}' is already running (pid {})", repo, process.info.pid));
+        }
+
+        let mut cmd = Command::new(&spec.program);
+        cmd.args(&spec.args)
+            .current_dir(&spec.working_dir)
+            .envs(spec.env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // Hide console window on Windows
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+        // Own process group so stop_repo can take down everything the launcher started
+        #[cfg(not(target_os = "windows"))]
+        cmd.process_group(0);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to start '{}': {}", spec.display(), e))?;
+        let pid = child.id().ok_or("Launched process exited immediately")?;
+
+        let info = RunningRepo {
+            repo: repo.to_string(),
+            pid,
+            started_at: Utc::now(),
+            command: spec.display(),
+        };
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let (exited_tx, exited) = watch::channel(false);
+        slot.process = Some(ManagedProcess { info: info.clone(), generation, stop_requested: false, exited });
+        slot.last_spec = Some(spec);
+        drop(repos);
+
+        if let Some(stdout) = child.stdout.take() {
+            tauri::async_runtime::spawn(capture_output(app_handle.clone(), repo.to_string(), "stdout", stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tauri::async_runtime::spawn(capture_output(app_handle.clone(), repo.to_string(), "stderr", stderr));
+        }
+
+        let app = app_handle.clone();
+        let repo_name = repo.to_string();
+        tauri::async_runtime::spawn(async move {
+            let status = child.wait().await;
+            let stopped = app.state::<AppState>().supervisor.mark_exited(&repo_name, generation);
+            let _ = exited_tx.send(true);
+
+            let (exit_code, success) = match status {
+                Ok(status) => (status.code(), status.success()),
+                Err(_) => (None, false),
+            };
+            log::info!("Repository '{}' (pid {}) exited with {:?}", repo_name, pid, exit_code);
+            let _ = app.emit("repo-exited", RepoExited { repo: repo_name, pid, exit_code, success, stopped });
+        });
+
+        Ok(info)
+    }
+
+    pub(crate) async fn stop(&self, repo: &str) -> Result<(), String> {
+        let (pid, mut exited) = {
+            let mut repos = self.repos.lock().map_err(|_| "Supervisor state poisoned")?;
+            let process = repos
+                .get_mut(repo)
+                .and_then(|slot| slot.process.as_mut())
+                .ok_or_else(|| format!("Repository '{}' is not running", repo))?;
+            process.stop_requested = true;
+            (process.info.pid, process.exited.clone())
+        };
+
+        let mut leader = exited.clone();
+        terminate_process_tree(pid, wait_exited(&mut leader)).await;
+        tokio::time::timeout(STOP_TIMEOUT, wait_exited(&mut exited))
+            .await
+            .map_err(|_| format!("Repository '{}' (pid {}) did not exit in time", repo, pid))
+    }
+
+    pub(crate) async fn restart(&self, app_handle: &tauri::AppHandle, repo: &str) -> Result<RunningRepo, String> {
+        let (running, spec) = {
+            let repos = self.repos.lock().map_err(|_| "Supervisor state poisoned")?;
+            let slot = repos.get(repo).ok_or_else(|| format!("Repository '{}' was never started", repo))?;
+            let spec = slot.last_spec.clone().ok_or_else(|| format!("Repository '{}' was never started", repo))?;
+            (slot.process.is_some(), spec)
+        };
+        if running {
+            self.stop(repo).await?;
+        }
+        self.start(app_handle, repo, spec)
+    }
+
+    // Fire-and-forget stop for app shutdown
+    pub(crate) fn stop_all(&self) {
+        let pids: Vec<u32> = match self.repos.lock() {
+            Ok(mut repos) => repos
+                .values_mut()
+                .filter_map(|slot| slot.process.as_mut())
+                .map(|process| {
+                    process.stop_requested = true;
+                    process.info.pid
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        terminate_process_trees_blocking(&pids, SHUTDOWN_GRACE);
+    }
+
+    pub(crate) fn list(&self) -> Vec<RunningRepo> {
+        let mut list: Vec<RunningRepo> = match self.repos.lock() {
+            Ok(repos) => repos.values().filter_map(|slot| slot.process.as_ref().map(|p| p.info.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        list.sort_by(|a, b| a.repo.cmp(&b.repo));
+        list
+    }
+
+    // Lines with seq greater than `since`, so the UI can poll incrementally
+    pub(crate) fn output(&self, repo: &str, since: Option<u64>) -> Vec<RepoOutputLine> {
+        let repos = match self.repos.lock() {
+            Ok(repos) => repos,
+            Err(_) => return Vec::new(),
+        };
+        match repos.get(repo) {
+            Some(slot) => slot
+                .output
+                .iter()
+                .filter(|line| since.map(|s| line.seq > s).unwrap_or(true))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn push_output(&self, repo: &str, stream: &str, data: String) {
+        if let Ok(mut repos) = self.repos.lock() {
+            let slot = repos.entry(repo.to_string()).or_default();
+            slot.next_seq += 1;
+            slot.output.push_back(RepoOutputLine {
+                seq: slot.next_seq,
+                timestamp: Utc::now(),
+                stream: stream.to_string(),
+                data,
+            });
+            while slot.output.len() > OUTPUT_BUFFER_LINES {
+                slot.output.pop_front();
+            }
+        }
+    }
+
+    // Clears the process entry if it still belongs to this launch; returns whether a stop was requested
+    fn mark_exited(&self, repo: &str, generation: u64) -> bool {
+        let mut repos = match self.repos.lock() {
+            Ok(repos) => repos,
+            Err(_) => return false,
+        };
+        let Some(slot) = repos.get_mut(repo) else { return false };
+        match &slot.process {
+            Some(process) if process.generation == generation => {
+                let stopped = process.stop_requested;
+                slot.process = None;
+                stopped
+            }
+            _ => false,
+        }
+    }
+}
+
+async fn wait_exited(exited: &mut watch::Receiver<bool>) {
+    while !*exited.borrow_and_update() {
+        if exited.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn capture_output<R: AsyncRead + Unpin>(app_handle: tauri::AppHandle, repo: String, stream: &'static str, reader: R) {
+    // Read raw bytes: launchers on Windows often print in the console code page, not UTF-8
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match read_line_capped(&mut reader, &mut buf, MAX_LINE_BYTES).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_string();
+                app_handle.state::<AppState>().supervisor.push_output(&repo, stream, line);
+            }
+        }
+    }
+}
+
+// Like read_until(b'\n') but keeps at most `limit` bytes; the rest of a longer line is read and
+// dropped, so a server that never prints a newline cannot grow the buffer without bound
+async fn read_line_capped<R: AsyncBufRead + Unpin>(reader: &mut R, buf: &mut Vec<u8>, limit: usize) -> std::io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(read);
+        }
+        let (chunk, done) = match available.iter().position(|b| *b == b'\n') {
+            Some(end) => (&available[..=end], true),
+            None => (available, false),
+        };
+        let room = limit.saturating_sub(buf.len());
+        buf.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        let used = chunk.len();
+        reader.consume(used);
+        read += used;
+        if done {
+            return Ok(read);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn lines(input: &[u8], limit: usize) -> Vec<Vec<u8>> {
+        let mut reader = BufReader::with_capacity(4, input);
+        let mut buf = Vec::new();
+        let mut lines = Vec::new();
+        while read_line_capped(&mut reader, &mut buf, limit).await.unwrap() > 0 {
+            lines.push(std::mem::take(&mut buf));
+        }
+        lines
+    }
+
+    #[tokio::test]
+    async fn long_lines_are_cut_at_the_limit() {
+        let input = [b"short\n".as_slice(), &[b'x'; 40], b"\nnext\ntail"].concat();
+        let lines = lines(&input, 16).await;
+        assert_eq!(lines, vec![b"short\n".to_vec(), vec![b'x'; 16], b"next\n".to_vec(), b"tail".to_vec()]);
+    }
+
+    #[test]
+    fn output_keeps_only_the_newest_lines() {
+        let supervisor = Supervisor::new();
+        for i in 0..OUTPUT_BUFFER_LINES + 5 {
+            supervisor.push_output("demo", "stdout", format!("line {}", i));
+        }
+        let output = supervisor.output("demo", None);
+        assert_eq!(output.len(), OUTPUT_BUFFER_LINES);
+        assert_eq!(output[0].data, "line 5");
+        assert_eq!(output[0].seq, 6);
+        assert_eq!(output.last().unwrap().seq, (OUTPUT_BUFFER_LINES + 5) as u64);
+    }
+
+    #[test]
+    fn output_since_returns_later_lines_in_order() {
+        let supervisor = Supervisor::new();
+        supervisor.push_output("demo", "stdout", "one".to_string());
+        supervisor.push_output("demo", "stderr", "two".to_string());
+        supervisor.push_output("other", "stdout", "elsewhere".to_string());
+        supervisor.push_output("demo", "stdout", "three".to_string());
+
+        let all: Vec<_> = supervisor.output("demo", None).into_iter().map(|l| (l.seq, l.stream, l.data)).collect();
+        assert_eq!(
+            all,
+            vec![(1, "stdout".to_string(), "one".to_string()), (2, "stderr".to_string(), "two".to_string()), (3, "stdout".to_string(), "three".to_string())]
+        );
+        let since: Vec<_> = supervisor.output("demo", Some(1)).into_iter().map(|l| l.data).collect();
+        assert_eq!(since, ["two", "three"]);
+        assert!(supervisor.output("demo", Some(3)).is_empty());
+        assert!(supervisor.output("missing", None).is_empty());
+    }
+}
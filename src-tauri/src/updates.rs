@@ -0,0 +1,361 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+const DOWNLOAD_PROGRESS_EVENT: &str = "update-download-progress";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+const STAGED_BINARY: &str = "staged-update.bin";
+const STAGED_MANIFEST: &str = "staged-update.json";
+
+// A downloaded, signature-verified update waiting to be installed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StagedUpdate {
+    pub(crate) version: String,
+    pub(crate) current_version: String,
+    pub(crate) downloaded_at: DateTime<Utc>,
+    pub(crate) size: u64,
+    pub(crate) notes: Option<String>,
+    // Release signature of the package, checked again before it is installed at startup
+    #[serde(default)]
+    pub(crate) signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DownloadProgress {
+    pub(crate) downloaded: u64,
+    pub(crate) total: Option<u64>,
+}
+
+fn staging_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle.path().app_local_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join("updates"))
+}
+
+async fn check(app_handle: &tauri::AppHandle) -> Result<Option<Update>, String> {
+    let updater = app_handle.updater().map_err(|e| format!("Updater not available: {}", e))?;
+    updater.check().await.map_err(|e| format!("Failed to check for updates: {}", e))
+}
+
+// Downloads the package (the updater verifies its signature) while emitting update-download-progress
+async fn download(app_handle: &tauri::AppHandle, update: &Update) -> Result<Vec<u8>, String> {
+    let mut downloaded: u64 = 0;
+    let mut last_emit: Option<Instant> = None;
+    let progress_handle = app_handle.clone();
+    let bytes = update
+        .download(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                if last_emit.map(|t| t.elapsed() >= PROGRESS_INTERVAL).unwrap_or(true) {
+                    last_emit = Some(Instant::now());
+                    let _ = progress_handle.emit(DOWNLOAD_PROGRESS_EVENT, DownloadProgress { downloaded, total });
+                }
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| format!("Failed to download update: {}", e))?;
+    // Throttling may have skipped the last chunk
+    let size = bytes.len() as u64;
+    let _ = app_handle.emit(DOWNLOAD_PROGRESS_EVENT, DownloadProgress { downloaded: size, total: Some(size) });
+    Ok(bytes)
+}
+
+// "Download and verify now": fetches the latest release and keeps it for the next launch
+pub(crate) async fn download_and_stage(app_handle: &tauri::AppHandle) -> Result<StagedUpdate, String> {
+    let update = check(app_handle).await?.ok_or("No update available")?;
+    if let Some(staged) = staged(app_handle) {
+        if staged.version == update.version {
+            return Ok(staged);
+        }
+    }
+
+    let bytes = download(app_handle, &update).await?;
+    let staged = StagedUpdate {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        downloaded_at: Utc::now(),
+        size: bytes.len() as u64,
+        notes: update.body.clone(),
+        signature: update.signature.clone(),
+    };
+
+    let dir = staging_dir(app_handle)?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    // Binary first, manifest last: a manifest on disk always points at a complete file
+    let tmp = dir.join(format!("{}.tmp", STAGED_BINARY));
+    std::fs::write(&tmp, &bytes)
+        .and_then(|_| std::fs::rename(&tmp, dir.join(STAGED_BINARY)))
+        .map_err(|e| format!("Failed to stage update: {}", e))?;
+    let manifest = serde_json::to_vec_pretty(&staged).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(STAGED_MANIFEST), manifest).map_err(|e| format!("Failed to stage update: {}", e))?;
+
+    log::info!("Staged update {} ({} bytes)", staged.version, staged.size);
+    Ok(staged)
+}
+
+// The staged update, unless it is missing, incomplete or already the running version
+pub(crate) fn staged(app_handle: &tauri::AppHandle) -> Option<StagedUpdate> {
+    let dir = staging_dir(app_handle).ok()?;
+    let raw = std::fs::read(dir.join(STAGED_MANIFEST)).ok()?;
+    let staged: StagedUpdate = serde_json::from_slice(&raw).ok()?;
+    let size = std::fs::metadata(dir.join(STAGED_BINARY)).map(|m| m.len()).ok();
+    let running = app_handle.package_info().version.to_string();
+    if size != Some(staged.size) || staged.version == running {
+        discard(app_handle);
+        return None;
+    }
+    Some(staged)
+}
+
+pub(crate) fn discard(app_handle: &tauri::AppHandle) {
+    if let Ok(dir) = staging_dir(app_handle) {
+        let _ = std::fs::remove_file(dir.join(STAGED_MANIFEST));
+        let _ = std::fs::remove_file(dir.join(STAGED_BINARY));
+    }
+}
+
+// Installs from the staged package when it matches the current release, downloading otherwise
+pub(crate) async fn install_now(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let update = check(app_handle).await?.ok_or("No update available")?;
+    let bytes = match staged_bytes(app_handle, &update) {
+        Some(bytes) => bytes,
+        None => download(app_handle, &update).await?,
+    };
+    discard(app_handle);
+    update.install(bytes).map_err(|e| format!("Failed to install update: {}", e))
+}
+
+fn staged_bytes(app_handle: &tauri::AppHandle, update: &Update) -> Option<Vec<u8>> {
+    let staged = staged(app_handle)?;
+    if staged.version != update.version {
+        // A newer release superseded the staged one
+        discard(app_handle);
+        return None;
+    }
+    std::fs::read(staging_dir(app_handle).ok()?.join(STAGED_BINARY)).ok()
+}
+
+// "Apply on next launch": called at the start of setup, before any job can run. Uses only the
+// staged package and its recorded signature, so it works offline; when the package cannot be
+// installed here it stays staged for the next launch or "Install now". Returns true when the
+// installer was started and the app was asked to exit, in which case setup should stop.
+pub(crate) fn apply_staged_at_startup(app_handle: &tauri::AppHandle) -> bool {
+    let Some(staged) = staged(app_handle) else { return false };
+    let bytes = match staging_dir(app_handle).and_then(|dir| std::fs::read(dir.join(STAGED_BINARY)).map_err(|e| e.to_string())) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::warn!("Staged update {} not applied: {}", staged.version, e);
+            return false;
+        }
+    };
+    if let Err(e) = updater_pubkey(app_handle).and_then(|pubkey| verify_signature(&bytes, &staged.signature, &pubkey)) {
+        log::warn!("Discarding staged update {}: {}", staged.version, e);
+        discard(app_handle);
+        return false;
+    }
+    let Some(kind) = package_kind(&bytes) else {
+        log::warn!("Staged update {} not applied: the package is not a Windows installer", staged.version);
+        return false;
+    };
+    if !cfg!(target_os = "windows") {
+        log::info!("Staged update {} is only applied at startup on Windows; use Install now", staged.version);
+        return false;
+    }
+
+    log::info!("Applying staged update {}", staged.version);
+    match launch_installer(&kind, &bytes, &windows_install_config(app_handle)) {
+        Ok(()) => {
+            discard(app_handle);
+            // The installer replaces this executable and starts the new version
+            app_handle.exit(0);
+            true
+        }
+        Err(e) => {
+            log::warn!("Staged update {} not applied: {}", staged.version, e);
+            false
+        }
+    }
+}
+
+fn updater_pubkey(app_handle: &tauri::AppHandle) -> Result<String, String> {
+    app_handle
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| "No updater public key configured".to_string())
+}
+
+// Same check the updater applies after a download: base64-wrapped minisign key and signature
+fn verify_signature(data: &[u8], signature: &str, pubkey: &str) -> Result<(), String> {
+    let decode = |value: &str| -> Result<String, String> {
+        let raw = base64::engine::general_purpose::STANDARD.decode(value).map_err(|e| e.to_string())?;
+        String::from_utf8(raw).map_err(|e| e.to_string())
+    };
+    let public_key = PublicKey::decode(&decode(pubkey)?).map_err(|e| format!("Invalid public key: {}", e))?;
+    let signature = Signature::decode(&decode(signature)?).map_err(|e| format!("Invalid signature: {}", e))?;
+    public_key.verify(data, &signature, true).map_err(|e| format!("Signature check failed: {}", e))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PackageKind {
+    Nsis,
+    Msi,
+}
+
+fn package_kind(bytes: &[u8]) -> Option<PackageKind> {
+    const MSI_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+    if bytes.starts_with(b"MZ") {
+        Some(PackageKind::Nsis)
+    } else if bytes.starts_with(&MSI_MAGIC) {
+        Some(PackageKind::Msi)
+    } else {
+        None
+    }
+}
+
+// plugins.updater.windows in tauri.conf.json, the settings the updater plugin installs with
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WindowsInstallConfig {
+    #[serde(default, alias = "install-mode")]
+    install_mode: InstallMode,
+    #[serde(default, alias = "installer-args")]
+    installer_args: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum InstallMode {
+    BasicUi,
+    Quiet,
+    #[default]
+    Passive,
+}
+
+fn windows_install_config(app_handle: &tauri::AppHandle) -> WindowsInstallConfig {
+    let windows = app_handle.config().plugins.0.get("updater").and_then(|updater| updater.get("windows")).cloned();
+    match windows.map(serde_json::from_value) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            log::warn!("Ignoring plugins.updater.windows: {}", e);
+            WindowsInstallConfig::default()
+        }
+        None => WindowsInstallConfig::default(),
+    }
+}
+
+// Program and arguments the updater plugin runs for a package in the configured install mode
+fn installer_command(kind: &PackageKind, path: &Path, config: &WindowsInstallConfig) -> (OsString, Vec<OsString>) {
+    let extra = config.installer_args.iter().map(OsString::from);
+    match kind {
+        PackageKind::Nsis => {
+            let mode: &[&str] = match config.install_mode {
+                InstallMode::Passive => &["/P", "/R"],
+                InstallMode::Quiet => &["/S", "/R"],
+                InstallMode::BasicUi => &[],
+            };
+            let args = mode.iter().map(OsString::from).chain([OsString::from("/UPDATE")]).chain(extra).collect();
+            (path.as_os_str().to_os_string(), args)
+        }
+        PackageKind::Msi => {
+            let mode = match config.install_mode {
+                InstallMode::Passive => "/passive",
+                InstallMode::Quiet => "/quiet",
+                InstallMode::BasicUi => "/qb+",
+            };
+            let args = [OsString::from("/i"), path.as_os_str().to_os_string(), mode.into(), "/promptrestart".into()]
+                .into_iter()
+                .chain(extra)
+                .chain([OsString::from("AUTOLAUNCHAPP=True")])
+                .collect();
+            (OsString::from("msiexec.exe"), args)
+        }
+    }
+}
+
+// A directory this call created, so nothing can be planted at the installer's path beforehand
+fn private_temp_dir() -> Result<PathBuf, String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to pick a temp folder: {}", e))?;
+    let name: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let dir = std::env::temp_dir().join(format!("portablesource-update-{}", name));
+    std::fs::create_dir(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+// Starts the installer without waiting for it
+fn launch_installer(kind: &PackageKind, bytes: &[u8], config: &WindowsInstallConfig) -> Result<(), String> {
+    let file_name = match kind {
+        PackageKind::Nsis => "portablesource-setup.exe",
+        PackageKind::Msi => "portablesource.msi",
+    };
+    let path = private_temp_dir()?.join(file_name);
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let (program, args) = installer_command(kind, &path, config);
+    std::process::Command::new(program)
+        .args(args)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to start installer: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const PUBKEY: &str = "dW50cnVzdGVkIGNvbW1lbnQ6IG1pbmlzaWduIHB1YmxpYyBrZXk6IEI3MzVGNjFDQzE2OUNFQTAKUldTZ3ptbkJIUFkxdHduU1d3bllTSTdpL3lHaEkyU0VFZWR0ZW5jTmkxb0ZGeDc0dXEvL2NZRzYK";
+
+    #[test]
+    fn package_kind_reads_the_header() {
+        assert_eq!(package_kind(b"MZ\x90\x00"), Some(PackageKind::Nsis));
+        assert_eq!(package_kind(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, 0]), Some(PackageKind::Msi));
+        assert_eq!(package_kind(b"PK\x03\x04"), None);
+        assert_eq!(package_kind(b""), None);
+    }
+
+    #[test]
+    fn unsigned_or_garbled_packages_are_rejected() {
+        assert!(verify_signature(b"package", "", PUBKEY).is_err());
+        assert!(verify_signature(b"package", "not base64!", PUBKEY).is_err());
+        let forged = base64::engine::general_purpose::STANDARD.encode("untrusted comment: x\nnot a signature\n");
+        assert!(verify_signature(b"package", &forged, PUBKEY).is_err());
+        assert!(verify_signature(b"package", &forged, "").is_err());
+    }
+
+    #[test]
+    fn installer_follows_the_configured_install_mode() {
+        let path = Path::new("C:\\Temp\\x\\setup.exe");
+        let args = |kind, raw: Value| installer_command(&kind, path, &serde_json::from_value(raw).unwrap()).1;
+
+        assert_eq!(args(PackageKind::Nsis, json!({})), ["/P", "/R", "/UPDATE"]);
+        assert_eq!(args(PackageKind::Nsis, json!({ "installMode": "quiet", "installerArgs": ["/D=x"] })), ["/S", "/R", "/UPDATE", "/D=x"]);
+        assert_eq!(args(PackageKind::Nsis, json!({ "installMode": "basicUi" })), ["/UPDATE"]);
+
+        let (program, msi) = installer_command(&PackageKind::Msi, path, &serde_json::from_value(json!({ "installMode": "quiet" })).unwrap());
+        assert_eq!(program, "msiexec.exe");
+        assert_eq!(msi, ["/i", "C:\\Temp\\x\\setup.exe", "/quiet", "/promptrestart", "AUTOLAUNCHAPP=True"]);
+    }
+
+    #[test]
+    fn each_installer_gets_its_own_folder() {
+        let first = private_temp_dir().unwrap();
+        let second = private_temp_dir().unwrap();
+        assert_ne!(first, second);
+        assert!(first.is_dir() && second.is_dir());
+        let _ = std::fs::remove_dir(&first);
+        let _ = std::fs::remove_dir(&second);
+    }
+}
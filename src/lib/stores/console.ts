@@ -41,6 +41,7 @@ export const filteredLogs = derived(consoleState, ($state) => {
 // Console service class
 class ConsoleService {
   private initialized = false;
+  private maxEntries = 1000;
 
   async init() {
     if (this.initialized) return;
@@ -48,7 +49,9 @@ class ConsoleService {
     try {
       // Load existing logs
       const logs: LogEntry[] = await invoke('get_console_logs');
-      const isEnabled: boolean = await invoke('is_console_enabled');
+      const settings: ConsoleSettings = await invoke('get_console_settings');
+      const isEnabled = settings.enabled;
+      this.maxEntries = settings.max_entries;
       
       consoleState.update(state => ({
         ...state,
@@ -98,10 +101,24 @@ class ConsoleService {
     }
   }
 
+  // Prepend entries persisted in <install>/logs from earlier sessions
+  async loadHistory(limit = 1000) {
+    try {
+      const history: LogEntry[] = await invoke('load_console_history', { limit });
+      consoleState.update(state => {
+        const oldest = state.logs.length > 0 ? state.logs[0].timestamp : null;
+        const older = oldest ? history.filter(entry => entry.timestamp < oldest) : history;
+        return { ...state, logs: [...older, ...state.logs] };
+      });
+    } catch (error) {
+      console.error('Failed to load log history:', error);
+    }
+  }
+
   async addLogEntry(entry: LogEntry) {
     consoleState.update(state => ({
       ...state,
-      logs: [...state.logs, entry]
+      logs: [...state.logs, entry].slice(-this.maxEntries)
     }));
   }
 
@@ -129,11 +146,14 @@ class ConsoleService {
 
   async updateSettings(settings: ConsoleSettings) {
     try {
-      await invoke('set_console_settings', { settings });
+      const saved: ConsoleSettings = await invoke('set_console_settings', { settings });
+      this.maxEntries = saved.max_entries;
       consoleState.update(state => ({
         ...state,
-        isEnabled: settings.enabled
+        isEnabled: saved.enabled,
+        logs: state.logs.slice(-saved.max_entries)
       }));
+      return saved;
     } catch (error) {
       console.error('Failed to update console settings:', error);
       throw error;
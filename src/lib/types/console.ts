@@ -9,7 +9,7 @@ export interface LogEntry {
 export interface ConsoleSettings {
   enabled: boolean;
   max_entries: number;
-  log_level: string;
+  log_level: LogLevel;
 }
 
 export type LogLevel = 'debug' | 'info' | 'warn' | 'error';